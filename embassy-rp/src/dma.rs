@@ -211,6 +211,8 @@ static CHANNEL_WAKERS: [AtomicWaker; CHANNEL_COUNT] = [NEW_AW; CHANNEL_COUNT];
 mod sealed {
     pub trait Channel {}
 
+    pub trait PacingTimer {}
+
     pub trait Word {}
 }
 
@@ -230,6 +232,25 @@ pub trait Channel: Peripheral<P = Self> + sealed::Channel + Into<AnyChannel> + S
     }
 }
 
+/// DMA pacing timer, which requests a transfer every `Y / X` `clk_sys` cycles.
+pub trait PacingTimer: Peripheral<P = Self> + sealed::PacingTimer + Sized + 'static {
+    /// Timer number.
+    fn number(&self) -> u8;
+
+    /// Set the request rate to `clk_sys * x / y`, with `x <= y`.
+    fn set_fraction(&self, x: u16, y: u16) {
+        pac::DMA.timer(self.number() as _).write(|w| {
+            w.set_x(x);
+            w.set_y(y);
+        });
+    }
+
+    /// Transfer request signal of this timer, to pace a transfer with.
+    fn treq(&self) -> u8 {
+        vals::TreqSel::TIMER0.0 + self.number()
+    }
+}
+
 /// DMA word.
 pub trait Word: sealed::Word {
     /// Word size.
@@ -311,3 +332,19 @@ channel!(DMA_CH8, 8);
 channel!(DMA_CH9, 9);
 channel!(DMA_CH10, 10);
 channel!(DMA_CH11, 11);
+
+macro_rules! pacing_timer {
+    ($name:ident, $num:expr) => {
+        impl sealed::PacingTimer for peripherals::$name {}
+        impl PacingTimer for peripherals::$name {
+            fn number(&self) -> u8 {
+                $num
+            }
+        }
+    };
+}
+
+pacing_timer!(DMA_TIMER0, 0);
+pacing_timer!(DMA_TIMER1, 1);
+pacing_timer!(DMA_TIMER2, 2);
+pacing_timer!(DMA_TIMER3, 3);
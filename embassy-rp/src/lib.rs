@@ -183,6 +183,11 @@ embassy_hal_internal::peripherals! {
     DMA_CH10,
     DMA_CH11,
 
+    DMA_TIMER0,
+    DMA_TIMER1,
+    DMA_TIMER2,
+    DMA_TIMER3,
+
     PWM_CH0,
     PWM_CH1,
     PWM_CH2,
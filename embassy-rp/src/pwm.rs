@@ -1,14 +1,26 @@
 //! Pulse Width Modulation (PWM)
 
+use core::cell::Cell;
+use core::future::poll_fn;
+use core::marker::PhantomData;
+use core::task::Poll;
+
+use critical_section::Mutex;
+use embassy_hal_internal::drop::OnDrop;
 use embassy_hal_internal::{into_ref, Peripheral, PeripheralRef};
+use embassy_sync::waitqueue::AtomicWaker;
+use embassy_time::{with_timeout, Duration, Instant, Ticker, Timer};
 use fixed::traits::ToFixed;
 use fixed::FixedU16;
-use pac::pwm::regs::{ChDiv, Intr};
+use pac::io::vals::Outover;
+use pac::pwm::regs::{ChCc, ChCsr, ChDiv, Intr};
 use pac::pwm::vals::Divmode;
 
 use crate::gpio::sealed::Pin as _;
-use crate::gpio::{AnyPin, Pin as GpioPin};
-use crate::{pac, peripherals, RegExt};
+use crate::gpio::{AnyPin, Drive, Input, Level, Pin as GpioPin, Pull, SlewRate};
+use crate::interrupt::typelevel::Binding;
+use crate::interrupt::InterruptExt;
+use crate::{clocks, dma, interrupt, pac, peripherals, RegExt};
 
 /// The configuration of a PWM slice.
 /// Note the period in clock cycles of a slice can be computed as:
@@ -46,6 +58,36 @@ pub struct Config {
     pub top: u16,
 }
 
+impl Config {
+    /// Create a config with at least `min_bits` of duty resolution, running as close to
+    /// `approx_freq_hz` as that allows.
+    ///
+    /// `sys_clk_hz` is the `clk_sys` frequency the slice will run from. The divider is kept as
+    /// small as possible to maximize `top`, and `top + 1` is never below `2^min_bits`, so the
+    /// frequency lands below `approx_freq_hz` when the requested resolution can't reach it.
+    /// Returns the config together with the achieved frequency in Hz and the achieved duty
+    /// resolution in bits, which may exceed `min_bits`. Both compare values start at 0.
+    pub fn for_resolution(sys_clk_hz: u32, min_bits: u8, approx_freq_hz: u32) -> (Self, u32, u8) {
+        assert!((1..=16).contains(&min_bits), "PWM resolution must be 1 to 16 bits");
+        assert!(approx_freq_hz > 0, "PWM frequency must not be zero");
+
+        // Period in units of 1/16 clock cycles, matching the 4 fractional divider bits.
+        let period16 = sys_clk_hz as u64 * 16 / approx_freq_hz as u64;
+        let div16 = ((period16 + 0xFFFF) / 0x10000).clamp(16, 0xFFF);
+        let counts = (period16 / div16).clamp(1 << min_bits, 0x10000);
+
+        let freq_hz = (sys_clk_hz as u64 * 16 / (div16 * counts)) as u32;
+        let bits = (63 - counts.leading_zeros()) as u8;
+
+        let config = Self {
+            divider: FixedU16::from_bits(div16 as u16),
+            top: (counts - 1) as u16,
+            ..Self::default()
+        };
+        (config, freq_hz, bits)
+    }
+}
+
 impl Default for Config {
     fn default() -> Self {
         Self {
@@ -81,6 +123,57 @@ impl From<InputMode> for Divmode {
     }
 }
 
+/// PWM error.
+#[derive(Debug, Eq, PartialEq, Copy, Clone)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Error {
+    /// No complete pulse arrived in time, or it was too long to measure.
+    Timeout,
+}
+
+/// Output channel of a PWM slice.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum PwmOutput {
+    /// Channel A.
+    A,
+    /// Channel B.
+    B,
+}
+
+/// Frequency progression of [`Pwm::sweep`].
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum SweepMode {
+    /// The frequency changes by the same number of Hz in each step.
+    Linear,
+    /// The frequency changes by the same ratio in each step, spending equal time per octave.
+    Logarithmic,
+}
+
+/// Brightness curve of [`Pwm::breathe`].
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum BreatheCurve {
+    /// The duty cycle ramps linearly up and down.
+    Triangle,
+    /// The duty cycle follows a raised cosine, lingering at the dark and bright ends.
+    Sine,
+    /// A triangle with gamma 2.2 correction, so the brightness appears to ramp linearly.
+    Gamma,
+}
+
+/// Register snapshot of a PWM slice, see [`Pwm::save`] and [`Pwm::restore`].
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct PwmSliceState {
+    csr: u32,
+    div: u32,
+    top: u16,
+    cc: u32,
+    ctr: u16,
+}
+
 /// PWM driver.
 pub struct Pwm<'d, T: Channel> {
     inner: PeripheralRef<'d, T>,
@@ -189,6 +282,235 @@ impl<'d, T: Channel> Pwm<'d, T> {
         Self::configure(self.inner.regs(), config);
     }
 
+    /// Switch to a precomputed config at a wrap, without a mixed period.
+    ///
+    /// The compare values and `top` are double-buffered and take effect at the next wrap. The
+    /// divider, the inversion and the phase-correct setting are not buffered, so they must be
+    /// written right after that same wrap. To make sure the buffered values are not latched a
+    /// wrap earlier than expected, this first waits for a wrap, then writes the compare values
+    /// and `top` at the start of a period, waits for the following wrap and writes the rest, a
+    /// few `clk_sys` cycles into the new period. This blocks for up to two periods. The wraps
+    /// are detected through [`Pwm::periods_elapsed`] if the period counter is running, and
+    /// otherwise from the raw wrap flag, which is cleared. A stopped slice takes the whole
+    /// config at once.
+    pub fn swap_config(&mut self, next: &Config) {
+        if next.divider > FixedU16::<fixed::types::extra::U4>::from_bits(0xFFF) {
+            panic!("Requested divider is too large");
+        }
+
+        let p = self.inner.regs();
+        if !p.csr().read().en() {
+            Self::configure(p, next);
+            return;
+        }
+
+        // Sync to a wrap, so the buffered values below latch at the wrap waited for next.
+        if !self.period_counting() {
+            self.clear_wrapped();
+        }
+        self.block_until_wrap();
+
+        p.cc().write(|w| {
+            w.set_a(next.compare_a);
+            w.set_b(next.compare_b);
+        });
+        p.top().write(|w| w.set_top(next.top));
+
+        self.block_until_wrap();
+        Self::configure(p, next);
+    }
+
+    /// Block until the slice wraps.
+    ///
+    /// While the period counter is running this waits for [`Pwm::periods_elapsed`] to advance,
+    /// otherwise for the raw wrap flag, which is then cleared.
+    fn block_until_wrap(&mut self) {
+        if self.period_counting() {
+            let periods = self.periods_elapsed();
+            while self.periods_elapsed() == periods {}
+        } else {
+            while !self.wrapped() {}
+            self.clear_wrapped();
+        }
+    }
+
+    /// Check whether the wrap interrupt of [`Pwm::start_period_counter`] is enabled, in which
+    /// case the raw wrap flag belongs to the interrupt handler.
+    fn period_counting(&self) -> bool {
+        pac::PWM.inte().read().0 & self.bit() != 0
+    }
+
+    /// Repeatedly output `on_pulses` complete periods, then stay low for `off`.
+    ///
+    /// This is meant for IR remote carriers and stepper bursts. Each burst starts from a
+    /// counter of 0 with the current `top`, divider and compare values. The count is exact, as
+    /// the compare values are cleared through their double buffer one period ahead, so the
+    /// output is already low when the slice is stopped after the last wrap. The wraps are
+    /// waited for on the wrap interrupt, so other tasks run during a burst, but the wrap
+    /// interrupt and this task must respond within one period, so run it at a high enough
+    /// priority for the carrier. The wrap interrupt of the slice is enabled during each burst
+    /// unless the period counter already enabled it. When the future is dropped the slice is
+    /// stopped with both compare values at 0, so the outputs are left low.
+    pub async fn burst(
+        &mut self,
+        _irq: impl Binding<interrupt::typelevel::PWM_IRQ_WRAP, InterruptHandler>,
+        on_pulses: u32,
+        off: Duration,
+    ) -> ! {
+        assert!(on_pulses > 0, "A burst needs at least one pulse");
+
+        let p = self.inner.regs();
+        let cc = p.cc().read();
+        let _low = OnDrop::new(|| {
+            p.csr().write_clear(|w| w.set_en(true));
+            p.cc().write_value(ChCc(0));
+        });
+
+        p.csr().write_clear(|w| w.set_en(true));
+        loop {
+            self.emit_periods(on_pulses, cc).await;
+            Timer::after(off).await;
+        }
+    }
+
+    /// Wait for a rising edge on `trigger`, then output exactly `n` periods and stop.
+    ///
+    /// This is meant for strobe and camera sync. The slice is stopped with the counter at
+    /// `top` while waiting, which holds the outputs at their inactive level, and the burst runs
+    /// like one burst of [`Pwm::burst`]. The start latency is that of waking this task from the
+    /// GPIO interrupt, typically a few microseconds, and can be reduced by running it on a high
+    /// priority `InterruptExecutor`. Once the edge is seen, only a few register writes precede
+    /// the first period. The periods are counted on the wrap interrupt as in [`Pwm::burst`], so
+    /// other tasks keep running during the burst. Afterwards, or if the future is dropped, the
+    /// slice is stopped with the counter at `top` and the compare values restored, so the
+    /// outputs return to their inactive level unless a compare value exceeds `top`.
+    pub async fn trigger_burst(
+        &mut self,
+        _irq: impl Binding<interrupt::typelevel::PWM_IRQ_WRAP, InterruptHandler>,
+        trigger: &mut Input<'_>,
+        n: u32,
+    ) {
+        assert!(n > 0, "A burst needs at least one pulse");
+
+        let p = self.inner.regs();
+        let cc = p.cc().read();
+        let top = p.top().read().top();
+        let _idle = OnDrop::new(|| {
+            p.csr().write_clear(|w| w.set_en(true));
+            p.ctr().write(|w| w.set_ctr(top));
+            p.cc().write_value(cc);
+        });
+
+        p.csr().write_clear(|w| w.set_en(true));
+        p.ctr().write(|w| w.set_ctr(top));
+        trigger.wait_for_rising_edge().await;
+        self.emit_periods(n, cc).await;
+    }
+
+    /// Output exactly `n` periods with the compare values `cc` from a stopped slice, and stop
+    /// it again with the compare values at 0.
+    ///
+    /// Each wrap is waited for on the wrap interrupt, which is enabled for the duration unless
+    /// the period counter already enabled it.
+    async fn emit_periods(&mut self, n: u32, cc: ChCc) {
+        let p = self.inner.regs();
+
+        // The slice is stopped, so these take effect immediately.
+        p.ctr().write(|w| w.set_ctr(0));
+        p.cc().write_value(cc);
+
+        let bit = self.bit();
+        let enabled_here = !self.period_counting();
+        if enabled_here {
+            // Stale wraps must not be counted.
+            self.clear_wrapped();
+            pac::PWM.inte().write_set(|w| w.0 = bit);
+        }
+        let _irq = OnDrop::new(move || {
+            if enabled_here {
+                pac::PWM.inte().write_clear(|w| w.0 = bit);
+            }
+        });
+        interrupt::PWM_IRQ_WRAP.unpend();
+        unsafe { interrupt::PWM_IRQ_WRAP.enable() };
+
+        let start = self.periods_elapsed();
+        p.csr().write_set(|w| w.set_en(true));
+
+        self.wait_for_periods(start + n as u64 - 1).await;
+        // Latched at the last wrap, so the next period stays low.
+        p.cc().write_value(ChCc(0));
+        self.wait_for_periods(start + n as u64).await;
+        p.csr().write_clear(|w| w.set_en(true));
+    }
+
+    /// Wait until [`Pwm::periods_elapsed`] reaches `periods`, woken by the wrap interrupt.
+    async fn wait_for_periods(&self, periods: u64) {
+        let waker = &WRAP_WAKERS[self.inner.number() as usize];
+        poll_fn(|cx| {
+            waker.register(cx.waker());
+            if self.periods_elapsed() >= periods {
+                Poll::Ready(())
+            } else {
+                Poll::Pending
+            }
+        })
+        .await
+    }
+
+    /// Move channel A to another of its pins, e.g. from GPIO0 to GPIO16 for slice 0.
+    ///
+    /// The pin type guarantees at compile time that the new pin carries channel A of this
+    /// slice. The old pin is disconnected first, leaving it undriven at the level set by its
+    /// pull, and the new pin is then connected, so for a few `clk_sys` cycles neither pin is
+    /// driven. The slice keeps running, so the new pin continues the current period. The new
+    /// pin keeps its pad settings, see [`Pwm::set_pad_config`].
+    pub fn set_output_pin_a(&mut self, pin: impl Peripheral<P = impl PwmPinA<T>> + 'd) {
+        into_ref!(pin);
+        Self::replace_pin(&mut self.pin_a, pin.map_into());
+    }
+
+    /// Move channel B to another of its pins, e.g. from GPIO1 to GPIO17 for slice 0.
+    ///
+    /// This works like [`Pwm::set_output_pin_a`], also for a B pin used as input.
+    pub fn set_output_pin_b(&mut self, pin: impl Peripheral<P = impl PwmPinB<T>> + 'd) {
+        into_ref!(pin);
+        Self::replace_pin(&mut self.pin_b, pin.map_into());
+    }
+
+    fn replace_pin(slot: &mut Option<PeripheralRef<'d, AnyPin>>, pin: PeripheralRef<'d, AnyPin>) {
+        if let Some(old) = slot.take() {
+            old.gpio().ctrl().write(|w| w.set_funcsel(31));
+        }
+        pin.gpio().ctrl().write(|w| w.set_funcsel(4));
+        *slot = Some(pin);
+    }
+
+    /// Set the pad drive strength, slew rate and pull of the connected pins.
+    ///
+    /// The PWM always drives its pins push-pull, the pads have no open-drain mode. To drive an
+    /// open-drain bus segment, buffer the output with an external open-drain transistor and
+    /// pull-up, possibly inverting the output with `invert_a`/`invert_b`. The internal pull-up
+    /// (about 50 kΩ) is too weak to pull up a bus at PWM rates on its own, since the rise time
+    /// grows with the bus capacitance. A low drive strength with a slow slew rate limits the
+    /// edge rate and ringing on long lines, at the cost of slower edges.
+    pub fn set_pad_config(&mut self, drive: Drive, slew_rate: SlewRate, pull: Pull) {
+        let drive = match drive {
+            Drive::_2mA => pac::pads::vals::Drive::_2MA,
+            Drive::_4mA => pac::pads::vals::Drive::_4MA,
+            Drive::_8mA => pac::pads::vals::Drive::_8MA,
+            Drive::_12mA => pac::pads::vals::Drive::_12MA,
+        };
+        for pin in [&self.pin_a, &self.pin_b].into_iter().flatten() {
+            pin.pad_ctrl().modify(|w| {
+                w.set_drive(drive);
+                w.set_slewfast(slew_rate == SlewRate::Fast);
+                w.set_pue(pull == Pull::Up);
+                w.set_pde(pull == Pull::Down);
+            });
+        }
+    }
+
     fn configure(p: pac::pwm::Channel, config: &Config) {
         if config.divider > FixedU16::<fixed::types::extra::U4>::from_bits(0xFFF) {
             panic!("Requested divider is too large");
@@ -208,6 +530,620 @@ impl<'d, T: Channel> Pwm<'d, T> {
         });
     }
 
+    /// Sweep a 50% duty square wave from `f0_hz` to `f1_hz` over `duration`.
+    ///
+    /// The frequency is updated every millisecond following `mode`, and the output is left
+    /// playing `f1_hz` when the sweep completes. Both frequencies must be non-zero. The
+    /// reachable range is about `clk_sys / (65536 * 256)` to `clk_sys / 2`, e.g. 7.5 Hz to
+    /// 62.5 MHz at 125 MHz, halved in phase-correct mode. Frequencies outside it are clamped
+    /// as in [`Pwm::set_frequency`], which also blocks each update until the slice wraps, so
+    /// below about 2 kHz the updates fall behind and the sweep takes longer than
+    /// `duration`.
+    pub async fn sweep(&mut self, f0_hz: u32, f1_hz: u32, duration: Duration, mode: SweepMode) {
+        const STEP: Duration = Duration::from_millis(1);
+
+        assert!(f0_hz > 0 && f1_hz > 0, "Sweep frequencies must not be zero");
+
+        let steps = (duration.as_ticks() / STEP.as_ticks()).clamp(1, u32::MAX as u64) as u32;
+        let ratio = match mode {
+            SweepMode::Linear => 1.0,
+            SweepMode::Logarithmic => nth_root(f1_hz as f32 / f0_hz as f32, steps),
+        };
+
+        let mut ticker = Ticker::every(STEP);
+        let mut freq = f0_hz as f32;
+        for step in 0..steps {
+            // Rounding errors of the logarithmic steps must not reach 0 Hz.
+            self.play_tone((freq + 0.5).max(1.0) as u32);
+            ticker.next().await;
+
+            freq = match mode {
+                SweepMode::Linear => f0_hz as f32 + (f1_hz as f32 - f0_hz as f32) * (step + 1) as f32 / steps as f32,
+                SweepMode::Logarithmic => freq * ratio,
+            };
+        }
+        self.play_tone(f1_hz);
+    }
+
+    /// Continuously ramp the brightness of an LED on `output` up and down, once per `period`.
+    ///
+    /// The duty cycle is updated every 10 ms following `curve`, over the full `top` range. This
+    /// never returns. When the future is dropped, the compare value of `output` is set to 0, so
+    /// the LED is left off (the output low, or high if inverted).
+    pub async fn breathe(&mut self, output: PwmOutput, period: Duration, curve: BreatheCurve) -> ! {
+        const STEP: Duration = Duration::from_millis(10);
+
+        let handle = self.duty_handle(output);
+        let _off = OnDrop::new(|| handle.set_compare(0));
+
+        let period = period.as_ticks().max(1);
+        let start = Instant::now();
+        let mut ticker = Ticker::every(STEP);
+        loop {
+            let phase = ((Instant::now() - start).as_ticks() % period) as f32 / period as f32;
+            let ramp = if phase < 0.5 { 2.0 * phase } else { 2.0 - 2.0 * phase };
+            let level = match curve {
+                BreatheCurve::Triangle => ramp,
+                BreatheCurve::Sine => (1.0 + sin_pi(ramp - 0.5)) / 2.0,
+                // x^2.2 = x^2 * x^(1/5)
+                BreatheCurve::Gamma => ramp * ramp * nth_root(ramp, 5),
+            };
+
+            let full = handle.top() as f32 + 1.0;
+            handle.set_compare((level * full + 0.5).min(u16::MAX as f32) as u16);
+            ticker.next().await;
+        }
+    }
+
+    /// Set the perceived brightness of a backlight or LED on `output`, never going below `min`.
+    ///
+    /// `level` and `min` are brightness levels from 0 to 255. The effective level is the larger
+    /// of the two, mapped to the duty cycle through a gamma 2.2 curve, `(level / 255)^2.2`, the
+    /// same as [`BreatheCurve::Gamma`], so equal steps in `level` look like equal steps in
+    /// brightness. A `level` of 0 still lights the output at `min`, it is only turned off when
+    /// both are 0. Any non-zero effective level gives a compare value of at least 1, so dim
+    /// levels don't round down to off at low `top` values.
+    pub fn set_brightness(&mut self, output: PwmOutput, level: u8, min: u8) {
+        let level = level.max(min);
+        let handle = self.duty_handle(output);
+        if level == 0 {
+            handle.set_compare(0);
+            return;
+        }
+
+        let x = level as f32 / 255.0;
+        // x^2.2 = x^2 * x^(1/5)
+        let duty = x * x * nth_root(x, 5);
+        let full = handle.top() as f32 + 1.0;
+        handle.set_compare((duty * full + 0.5).clamp(1.0, u16::MAX as f32) as u16);
+    }
+
+    /// Output 8-bit `samples` as the duty cycle of channel A at `sample_rate_hz`.
+    ///
+    /// This turns the slice into a simple DAC when followed by a low-pass filter. Each sample is
+    /// scaled from 0 (always low) to 255 (always high) onto the compare range `0..=top + 1`,
+    /// and written to CC by `dma` together with the compare value channel B had when the stream
+    /// started, so B keeps its output, but changes to it while streaming are overwritten. The
+    /// writes are paced by `timer`, so the sample rate doesn't depend on the carrier.
+    ///
+    /// The carrier is set by the current `top` and divider and is left unchanged. It should be
+    /// well above the sample rate, so the filter corner can sit between the two: a small `top`
+    /// gives a high carrier at the cost of duty resolution, e.g. a `top` of 255 gives the full
+    /// 8 bits at a 488 kHz carrier from 125 MHz. A new sample takes effect at the next wrap. The
+    /// pacing timer divides `clk_sys` by a 16-bit fraction, so `sample_rate_hz` must be between
+    /// `clk_sys / 65535` and `clk_sys`, e.g. 1.9 kHz to 125 MHz, and is approximated to the
+    /// closest such fraction. The samples are scaled in chunks of 32 while the previous chunk
+    /// is being sent, and the next transfer starts on the next timer request, so the stream
+    /// stays on the sample grid as long as it is restarted within one sample period, which
+    /// holds at audio rates. The slice must be enabled.
+    pub async fn stream_dac<C: dma::Channel, P: dma::PacingTimer>(
+        &mut self,
+        dma: impl Peripheral<P = C>,
+        timer: impl Peripheral<P = P>,
+        samples: &[u8],
+        sample_rate_hz: u32,
+    ) {
+        const CHUNK: usize = 32;
+
+        into_ref!(dma, timer);
+        let clk_sys = clocks::clk_sys_freq();
+        assert!(
+            sample_rate_hz <= clk_sys && sample_rate_hz as u64 * 0xFFFF >= clk_sys as u64,
+            "Sample rate out of range of the DMA pacing timer"
+        );
+
+        let (x, y) = pacing_fraction(sample_rate_hz, clk_sys);
+        timer.set_fraction(x, y);
+
+        let p = self.inner.regs();
+        let full = p.top().read().top() as u32 + 1;
+        let b = (p.cc().read().b() as u32) << 16;
+        let fill = |chunk: &mut [u32; CHUNK], samples: &[u8]| {
+            for (cc, &sample) in chunk.iter_mut().zip(samples) {
+                *cc = b | (sample as u32 * full / 255).min(0xFFFF);
+            }
+            samples.len()
+        };
+
+        let [mut current, mut next] = [[0u32; CHUNK]; 2];
+        let mut chunks = samples.chunks(CHUNK);
+        let Some(first) = chunks.next() else {
+            return;
+        };
+        let mut len = fill(&mut current, first);
+        loop {
+            // Safety: `current` outlives the transfer, which is awaited here and aborted on drop.
+            let transfer = unsafe {
+                dma::write(
+                    dma.reborrow(),
+                    &current[..len],
+                    p.cc().as_ptr() as *mut u32,
+                    timer.treq(),
+                )
+            };
+            let next_len = chunks.next().map(|chunk| fill(&mut next, chunk));
+            transfer.await;
+
+            match next_len {
+                Some(next_len) => {
+                    core::mem::swap(&mut current, &mut next);
+                    len = next_len;
+                }
+                None => return,
+            }
+        }
+    }
+
+    /// Send `grb` colors to a chain of WS2812 style LEDs, one 24-bit `0x00GGRRBB` value per LED.
+    ///
+    /// The slice is set to an 800 kHz carrier with a divider of 1, and a compare value per bit,
+    /// 0.4 µs high for a 0 and 0.8 µs high for a 1, is written by `dma` on every wrap. The
+    /// timing is within 10 ns of nominal at 125 MHz, well inside the ±150 ns the LEDs tolerate.
+    /// Both channels carry the signal. The whole frame is encoded into `bits` first, which must
+    /// hold 24 compare values per LED plus one, and sent in a single transfer, so there are no
+    /// gaps between LEDs. After the last LED, the line is held low for 300 µs so that all LED
+    /// variants latch the colors. The previous configuration of the slice is then restored,
+    /// see [`Pwm::restore`], also if the future is dropped early.
+    pub async fn write_ws2812<C: dma::Channel>(&mut self, dma: impl Peripheral<P = C>, grb: &[u32], bits: &mut [u16]) {
+        let len = grb.len() * 24 + 1;
+        assert!(bits.len() >= len, "The bit buffer holds 24 values per LED plus one");
+
+        let counts = clocks::clk_sys_freq() / 800_000;
+        let zero = (counts * 8 / 25) as u16;
+        let one = (counts * 16 / 25) as u16;
+
+        for (led, &color) in bits.chunks_exact_mut(24).zip(grb) {
+            for (i, bit) in led.iter_mut().enumerate() {
+                *bit = if color & (1 << (23 - i)) != 0 { one } else { zero };
+            }
+        }
+        // Leaves the line low once the frame is out.
+        bits[len - 1] = 0;
+
+        // DREQ_PWM_WRAP0 + slice number.
+        let dreq = 24 + self.inner.number();
+        let p = self.inner.regs();
+        let state = self.save();
+        let _restore = OnDrop::new(|| self.restore(state));
+
+        p.csr().write_clear(|w| {
+            w.set_en(true);
+            w.set_ph_correct(true);
+        });
+        p.div().write_value(ChDiv(1 << 4));
+        p.top().write(|w| w.set_top(counts as u16 - 1));
+        p.cc().write(|w| {
+            w.set_a(0);
+            w.set_b(0);
+        });
+        p.csr().write_set(|w| w.set_en(true));
+
+        // Safety: `bits` outlives the transfer, which is awaited here and aborted on drop.
+        unsafe { dma::write(dma, &bits[..len], p.cc().as_ptr() as *mut u16, dreq) }.await;
+
+        Timer::after_micros(300).await;
+    }
+
+    /// Force the outputs to their inactive level whenever `fault` is at the `active` level.
+    ///
+    /// The RP2040 has no hardware fault input for PWM, so this watches the pin asynchronously.
+    /// On assertion the connected pins are overridden at the pad to their inactive level
+    /// (taking `invert_a`/`invert_b` into account), which takes effect immediately instead of at
+    /// the next wrap, and the slice is then stopped with both compare values cleared. The
+    /// response latency is the interrupt latency plus the time until the executor polls this
+    /// future, typically a few microseconds. Run it on an interrupt executor to bound it.
+    ///
+    /// The outputs stay off after the fault is released. Drop the future, call
+    /// [`Pwm::clear_fault_shutdown`] and reconfigure the slice to resume.
+    pub async fn with_fault_shutdown(&mut self, fault: &mut Input<'_>, active: Level) -> ! {
+        loop {
+            match active {
+                Level::High => fault.wait_for_high().await,
+                Level::Low => fault.wait_for_low().await,
+            }
+            self.force_inactive();
+
+            match active {
+                Level::High => fault.wait_for_low().await,
+                Level::Low => fault.wait_for_high().await,
+            }
+        }
+    }
+
+    /// Release the pad override applied by [`Pwm::with_fault_shutdown`].
+    ///
+    /// The slice stays stopped, use [`Pwm::set_config`] or [`Pwm::enable`] to restart it.
+    pub fn clear_fault_shutdown(&mut self) {
+        for pin in [&self.pin_a, &self.pin_b].into_iter().flatten() {
+            pin.gpio().ctrl().modify(|w| w.set_outover(Outover::NORMAL));
+        }
+    }
+
+    fn force_inactive(&mut self) {
+        let csr = self.inner.regs().csr().read();
+        for (pin, inverted) in [(&self.pin_a, csr.a_inv()), (&self.pin_b, csr.b_inv())] {
+            if let Some(pin) = pin {
+                let level = if inverted { Outover::HIGH } else { Outover::LOW };
+                pin.gpio().ctrl().modify(|w| w.set_outover(level));
+            }
+        }
+
+        self.disable();
+        self.silence();
+    }
+
+    /// Measure the actual output frequency in Hz by looping it back into another slice.
+    ///
+    /// Jumper the output pin of this slice to `loopback_pin`, which must be the B pin of the
+    /// `capture` slice. The capture slice counts rising edges over a 10 ms gate, so the result
+    /// has a resolution of 100 Hz and blocks for the duration of the gate. This is meant for
+    /// bring-up tests verifying the divider and `top` settings against real hardware.
+    pub fn self_measure<C: Channel>(
+        &mut self,
+        capture: impl Peripheral<P = C>,
+        loopback_pin: impl Peripheral<P = impl PwmPinB<C>>,
+    ) -> u32 {
+        const GATE: Duration = Duration::from_millis(10);
+
+        let mut input = Pwm::new_input(capture, loopback_pin, InputMode::RisingEdge, Config::default());
+        input.clear_wrapped();
+
+        let start = Instant::now();
+        let mut wraps = 0u64;
+        while start.elapsed() < GATE {
+            if input.wrapped() {
+                input.clear_wrapped();
+                wraps += 1;
+            }
+        }
+        let counter = input.counter();
+        // A wrap may have happened between the last check and reading the counter.
+        if input.wrapped() && counter < 0x8000 {
+            wraps += 1;
+        }
+
+        let edges = wraps * 0x10000 + counter as u64;
+        (edges * 1_000_000 / GATE.as_micros()) as u32
+    }
+
+    /// Measure the width of a single high pulse on `pin`, e.g. the echo of an ultrasonic
+    /// distance sensor.
+    ///
+    /// `pin` must be a B pin of this slice, so create the slice without one, e.g. with
+    /// [`Pwm::new_free`]. For the measurement the slice is switched to [`InputMode::Level`],
+    /// where the counter only runs while the pin is high, so the width is timed by the counter
+    /// itself. This waits for the pin to be low, then for one complete high pulse, using the
+    /// GPIO interrupt for both edges, and returns its width. The end of the pulse stops the
+    /// counter in hardware, and the slice is stopped from the falling edge interrupt before a
+    /// following pulse could add to it, so the result doesn't depend on how quickly this task
+    /// is polled as long as the next pulse starts later than that interrupt latency.
+    ///
+    /// The divider and `top` are overwritten and the slice is left stopped in level mode. The
+    /// divider is chosen so a pulse as long as `timeout` fits in one counter period, giving a
+    /// resolution of `timeout / 65536` but at most one `clk_sys` cycle. Returns
+    /// [`Error::Timeout`] if no complete pulse arrived within `timeout`, or if the pulse
+    /// overflowed the counter, which can only happen with timeouts above `65536 * 256`
+    /// `clk_sys` cycles.
+    pub async fn measure_pulse(
+        &mut self,
+        pin: impl Peripheral<P = impl PwmPinB<T>>,
+        timeout: Duration,
+    ) -> Result<Duration, Error> {
+        into_ref!(pin);
+        let gpio = pin.gpio();
+        let mut pin = Input::new(pin, Pull::None);
+        // The slice only sees the pin with the PWM function selected. GPIO interrupts work
+        // with any function.
+        gpio.ctrl().write(|w| w.set_funcsel(4));
+
+        let p = self.inner.regs();
+        let clk = clocks::clk_sys_freq() as u64;
+        let timeout_cycles16 = clk * 16 * timeout.as_micros() / 1_000_000;
+        let div16 = ((timeout_cycles16 + 0xFFFF) / 0x10000).clamp(16, 0xFFF);
+
+        p.csr().write_clear(|w| w.set_en(true));
+        p.csr().modify(|w| w.set_divmode(Divmode::LEVEL));
+        p.div().write_value(ChDiv(div16 as u32));
+        p.top().write(|w| w.set_top(0xFFFF));
+
+        let _stop = OnDrop::new(|| p.csr().write_clear(|w| w.set_en(true)));
+
+        let measurement = async {
+            // Counting while the pin is still high is discarded below, so the counter can be
+            // running before the low level is seen, and the rising edge can't be missed.
+            p.ctr().write(|w| w.set_ctr(0));
+            p.csr().write_set(|w| w.set_en(true));
+            pin.wait_for_low().await;
+            p.ctr().write(|w| w.set_ctr(0));
+            self.clear_wrapped();
+
+            pin.wait_for_high().await;
+            pin.wait_for_low().await;
+            p.csr().write_clear(|w| w.set_en(true));
+        };
+        with_timeout(timeout, measurement).await.map_err(|_| Error::Timeout)?;
+
+        if self.wrapped() {
+            return Err(Error::Timeout);
+        }
+
+        let cycles16 = p.ctr().read().ctr() as u64 * div16;
+        Ok(Duration::from_nanos(cycles16 * 1_000_000_000 / (clk * 16)))
+    }
+
+    /// Stop the slice, keeping `top`, the compare values and the divider intact.
+    ///
+    /// The counter is halted and the outputs hold the level they had when the slice was
+    /// stopped. Call [`Pwm::silence`] first if the outputs must be at their inactive level.
+    /// Use [`Pwm::enable`] to resume without reconfiguring.
+    #[inline]
+    pub fn disable(&mut self) {
+        self.inner.regs().csr().write_clear(|w| w.set_en(true));
+    }
+
+    /// Resume a slice stopped with [`Pwm::disable`], counting on from where it stopped.
+    #[inline]
+    pub fn enable(&mut self) {
+        self.inner.regs().csr().write_set(|w| w.set_en(true));
+    }
+
+    /// Check whether the slice is enabled.
+    ///
+    /// This reads the global EN register, so it also reflects slices enabled through
+    /// [`PwmBatch::set_enabled`].
+    #[inline]
+    pub fn is_enabled(&self) -> bool {
+        pac::PWM.en().read().0 & self.bit() != 0
+    }
+
+    /// Set the PWM output frequency in Hz, keeping the current duty cycle of both channels.
+    ///
+    /// `top` and the divider are chosen to give the finest duty resolution that can reach the
+    /// requested frequency from `clk_sys`, taking phase-correct mode into account. Frequencies
+    /// outside the reachable range are clamped to the nearest achievable one.
+    ///
+    /// On a running slice the change is made like [`Pwm::swap_config`]: the buffered `top` and
+    /// compare values are written first and the divider right after the wrap that latches them,
+    /// so no period runs with a mix of the old and new timing. This blocks for up to two
+    /// periods of the old frequency.
+    pub fn set_frequency(&mut self, freq_hz: u32) {
+        let p = self.inner.regs();
+        let (divider, top) = timing_for_frequency(freq_hz, p.csr().read().ph_correct());
+
+        let old_top = p.top().read().top() as u32 + 1;
+        let new_top = top as u32 + 1;
+        let scale = |compare: u16| (compare as u32 * new_top / old_top).min(0xFFFF) as u16;
+        let cc = p.cc().read();
+        let running = p.csr().read().en();
+
+        if running {
+            // Sync to a wrap, so the buffered values below latch at the wrap waited for next.
+            if !self.period_counting() {
+                self.clear_wrapped();
+            }
+            self.block_until_wrap();
+        }
+
+        p.top().write(|w| w.set_top(top));
+        p.cc().write(|w| {
+            w.set_a(scale(cc.a()));
+            w.set_b(scale(cc.b()));
+        });
+
+        if running {
+            self.block_until_wrap();
+        }
+        p.div().write_value(ChDiv(divider.to_bits() as u32));
+    }
+
+    /// Set `top` and the clock divider together, without an intermediate period.
+    ///
+    /// `top` is double-buffered but the divider is not, so writing them separately to a running
+    /// slice briefly produces a mix of the old and new timing. Instead, the slice is stopped,
+    /// both values are written while it is stopped (where they take effect immediately), and it
+    /// is then restarted if it was running. The outputs hold their level during the few clock
+    /// cycles the slice is stopped. The compare values are left unchanged.
+    pub fn set_timing(&mut self, top: u16, divider: FixedU16<fixed::types::extra::U4>) {
+        if divider > FixedU16::<fixed::types::extra::U4>::from_bits(0xFFF) {
+            panic!("Requested divider is too large");
+        }
+
+        let p = self.inner.regs();
+        let was_enabled = p.csr().read().en();
+
+        p.csr().write_clear(|w| w.set_en(true));
+        p.div().write_value(ChDiv(divider.to_bits() as u32));
+        p.top().write(|w| w.set_top(top));
+        if was_enabled {
+            p.csr().write_set(|w| w.set_en(true));
+        }
+    }
+
+    /// Output 50% duty square waves at `freq_hz` on `leading` and `lagging`, 90° apart.
+    ///
+    /// Both slices are stopped, given the same timing in non phase-correct mode, and their
+    /// counters are preset a quarter period apart before they are restarted together through
+    /// [`PwmBatch::set_enabled`]. Since both slices then count from the same clock with the same
+    /// `top` and divider, the offset holds as long as neither is reconfigured on its own. To
+    /// change the frequency, call this again, which restarts both slices with the offset
+    /// restored.
+    ///
+    /// The quarter period is rounded to a whole counter step, so the phase is exact when
+    /// `top + 1` is a multiple of 4 and otherwise off by at most `180° / (top + 1)`.
+    pub fn start_quadrature<U: Channel>(leading: &mut Self, lagging: &mut Pwm<'_, U>, freq_hz: u32) {
+        let (divider, top) = timing_for_frequency(freq_hz, false);
+        let period = top as u32 + 1;
+        let half = (period / 2) as u16;
+        let quarter = ((period + 2) / 4) as u16;
+
+        PwmBatch::set_enabled(false, |batch| {
+            batch.enable(leading);
+            batch.enable(lagging);
+        });
+        for (p, ctr) in [(leading.inner.regs(), quarter), (lagging.inner.regs(), 0)] {
+            p.csr().write_clear(|w| w.set_ph_correct(true));
+            p.div().write_value(ChDiv(divider.to_bits() as u32));
+            p.top().write(|w| w.set_top(top));
+            p.cc().write(|w| {
+                w.set_a(half);
+                w.set_b(half);
+            });
+            p.ctr().write(|w| w.set_ctr(ctr));
+        }
+        PwmBatch::set_enabled(true, |batch| {
+            batch.enable(leading);
+            batch.enable(lagging);
+        });
+    }
+
+    /// Run this slice `fraction` of a period behind `master`, e.g. for interleaved converters.
+    ///
+    /// The divider, `top` and phase-correct setting of `master` are copied to this slice, the
+    /// compare values of both are kept. Both slices are stopped, this counter is set `fraction`
+    /// of a period behind the master counter, and both are restarted together through
+    /// [`PwmBatch::set_enabled`], so the offset holds as long as they count from the same
+    /// timing. If `master` is stopped, both are left stopped and must be started together with
+    /// [`PwmBatch::set_enabled`] to keep the offset. After changing the frequency of both,
+    /// call this again to restore the fraction, as the offset is kept in counter steps.
+    ///
+    /// The offset is rounded to a whole counter step, so the phase is off by at most
+    /// `180° / (top + 1)`, plus up to one step with a fractional divider. Only the non
+    /// phase-correct mode is supported, as the counting direction of a phase-correct slice
+    /// can't be set.
+    pub fn set_phase_offset<U: Channel>(&mut self, master: &Pwm<'_, U>, fraction: f32) {
+        let (p, m) = (self.inner.regs(), master.inner.regs());
+        let master_csr = m.csr().read();
+        assert!(
+            !master_csr.ph_correct(),
+            "Phase offsets require the non phase-correct mode"
+        );
+
+        PwmBatch::set_enabled(false, |batch| {
+            batch.enable(self);
+            batch.enable(master);
+        });
+
+        let top = m.top().read().top();
+        p.csr().write_clear(|w| w.set_ph_correct(true));
+        p.div().write_value(m.div().read());
+        p.top().write(|w| w.set_top(top));
+
+        let period = top as u32 + 1;
+        let fraction = fraction % 1.0;
+        let fraction = if fraction < 0.0 { fraction + 1.0 } else { fraction };
+        let offset = (fraction * period as f32 + 0.5) as u32 % period;
+        let ctr = (m.ctr().read().ctr() as u32 + period - offset) % period;
+        p.ctr().write(|w| w.set_ctr(ctr as u16));
+
+        if master_csr.en() {
+            PwmBatch::set_enabled(true, |batch| {
+                batch.enable(self);
+                batch.enable(master);
+            });
+        }
+    }
+
+    /// Drive complementary outputs on two slices, e.g. an H-bridge wired across slices.
+    ///
+    /// Both slices are stopped, given the same phase-correct timing for `freq_hz` and restarted
+    /// together through [`PwmBatch::set_enabled`], so they count in lockstep. `low_output` is
+    /// inverted and its compare value is kept `dead_time_ns` (rounded up to whole counter steps)
+    /// above the compare value of `high_output`, so in the centered pulses of phase-correct mode
+    /// both outputs are inactive for at least the dead time around every edge. The other output
+    /// of each slice follows the new timing with its own compare value. The pair starts at 50%
+    /// duty, use [`ComplementaryPair::set_duty`] to change it.
+    ///
+    /// The dead time holds as long as neither slice is reconfigured on its own, apart from a
+    /// few nanoseconds of pad delay mismatch between the pins.
+    pub fn start_complementary<U: Channel>(
+        high: &mut Self,
+        high_output: PwmOutput,
+        low: &mut Pwm<'d, U>,
+        low_output: PwmOutput,
+        freq_hz: u32,
+        dead_time_ns: u32,
+    ) -> ComplementaryPair<'d> {
+        let (divider, top) = timing_for_frequency(freq_hz, true);
+        let div16 = divider.to_bits() as u64;
+        let dead = (dead_time_ns as u64 * clocks::clk_sys_freq() as u64 * 16).div_ceil(div16 * 1_000_000_000);
+        let dead = dead.min(top as u64 + 1) as u16;
+
+        PwmBatch::set_enabled(false, |batch| {
+            batch.enable(high);
+            batch.enable(low);
+        });
+        for (p, output, inverted) in [
+            (high.inner.regs(), high_output, false),
+            (low.inner.regs(), low_output, true),
+        ] {
+            p.csr().modify(|w| {
+                w.set_ph_correct(true);
+                match output {
+                    PwmOutput::A => w.set_a_inv(inverted),
+                    PwmOutput::B => w.set_b_inv(inverted),
+                }
+            });
+            p.div().write_value(ChDiv(div16 as u32));
+            p.top().write(|w| w.set_top(top));
+            p.ctr().write(|w| w.set_ctr(0));
+        }
+
+        let pair = ComplementaryPair {
+            high: high.duty_handle(high_output),
+            low: low.duty_handle(low_output),
+            dead,
+        };
+        pair.set_duty(pair.max_duty() / 2);
+
+        PwmBatch::set_enabled(true, |batch| {
+            batch.enable(high);
+            batch.enable(low);
+        });
+        pair
+    }
+
+    /// Output a square wave with 50% duty cycle at `freq_hz` on both channels.
+    ///
+    /// This is meant for driving a piezo buzzer. Use [`Pwm::silence`] to stop the tone.
+    pub fn play_tone(&mut self, freq_hz: u32) {
+        self.set_frequency(freq_hz);
+
+        let p = self.inner.regs();
+        let half = ((p.top().read().top() as u32 + 1) / 2) as u16;
+        p.cc().write(|w| {
+            w.set_a(half);
+            w.set_b(half);
+        });
+    }
+
+    /// Silence the output by driving both channels to their inactive level.
+    pub fn silence(&mut self) {
+        self.inner.regs().cc().write(|w| {
+            w.set_a(0);
+            w.set_b(0);
+        });
+    }
+
     /// Advances a slice’s output phase by one count while it is running
     /// by inserting a pulse into the clock enable. The counter
     /// will not count faster than once per cycle.
@@ -240,6 +1176,71 @@ impl<'d, T: Channel> Pwm<'d, T> {
         self.inner.regs().ctr().write(|w| w.set_ctr(ctr))
     }
 
+    /// Capture the configuration and position of the slice.
+    ///
+    /// The snapshot holds CSR, DIV, TOP, CC and the counter, so [`Pwm::restore`] can bring the
+    /// slice back to the same state after the PWM block was reset or powered down.
+    pub fn save(&self) -> PwmSliceState {
+        let p = self.inner.regs();
+        PwmSliceState {
+            // PH_RET and PH_ADV are strobes, not state.
+            csr: p.csr().read().0 & 0x3F,
+            div: p.div().read().0,
+            top: p.top().read().top(),
+            cc: p.cc().read().0,
+            ctr: p.ctr().read().ctr(),
+        }
+    }
+
+    /// Restore a snapshot taken with [`Pwm::save`].
+    ///
+    /// The slice is stopped while DIV, TOP, CC and the counter are written, so they all take
+    /// effect at once, and CSR is written last. The slice is therefore running again afterwards
+    /// exactly if it was running when the snapshot was taken.
+    pub fn restore(&mut self, state: PwmSliceState) {
+        let p = self.inner.regs();
+        p.csr().write_clear(|w| w.set_en(true));
+        p.div().write_value(ChDiv(state.div));
+        p.top().write(|w| w.set_top(state.top));
+        p.cc().write_value(ChCc(state.cc));
+        p.ctr().write(|w| w.set_ctr(state.ctr));
+        p.csr().write_value(ChCsr(state.csr));
+    }
+
+    /// Start counting the periods of this slice, see [`Pwm::periods_elapsed`].
+    ///
+    /// The count is reset to 0 and the slice wrap interrupt is enabled. While counting, the
+    /// wrap flag is cleared by the interrupt handler, so [`Pwm::wrapped`] and
+    /// [`Pwm::wait_for_wrap`] must not be used on this slice.
+    pub fn start_period_counter(&mut self, _irq: impl Binding<interrupt::typelevel::PWM_IRQ_WRAP, InterruptHandler>) {
+        let number = self.inner.number() as usize;
+        critical_section::with(|cs| {
+            let counts = WRAP_COUNTS.borrow(cs);
+            let mut c = counts.get();
+            c[number] = 0;
+            counts.set(c);
+            self.clear_wrapped();
+            pac::PWM.inte().write_set(|w| w.0 = self.bit());
+        });
+
+        interrupt::PWM_IRQ_WRAP.unpend();
+        unsafe { interrupt::PWM_IRQ_WRAP.enable() };
+    }
+
+    /// Get the number of periods elapsed since [`Pwm::start_period_counter`].
+    ///
+    /// In phase-correct mode a period is a full up and down count. A wrap that is still pending
+    /// because interrupts are masked is included, so the count is exact as long as the wrap
+    /// interrupt is never held off for more than one period. Only one pending wrap per slice is
+    /// latched by the hardware, so further wraps during a longer masked window are lost.
+    pub fn periods_elapsed(&self) -> u64 {
+        critical_section::with(|cs| {
+            let counted = WRAP_COUNTS.borrow(cs).get()[self.inner.number() as usize];
+            let pending = pac::PWM.ints().read().0 & self.bit() != 0;
+            counted + pending as u64
+        })
+    }
+
     /// Wait for channel interrupt.
     #[inline]
     pub fn wait_for_wrap(&mut self) {
@@ -253,6 +1254,17 @@ impl<'d, T: Channel> Pwm<'d, T> {
         pac::PWM.intr().read().0 & self.bit() != 0
     }
 
+    /// Check if a wrap interrupt of this slice is pending.
+    ///
+    /// Unlike [`Pwm::wrapped`], which reads the raw flag, this reads the masked status, so it is
+    /// only set while the slice wrap interrupt is enabled, e.g. by [`Pwm::start_period_counter`].
+    /// Seeing it set outside the interrupt handler means the handler hasn't run yet, and a
+    /// further wrap before it does is lost. It is cleared with [`Pwm::clear_wrapped`].
+    #[inline]
+    pub fn wrap_pending(&self) -> bool {
+        pac::PWM.ints().read().0 & self.bit() != 0
+    }
+
     #[inline]
     /// Clear interrupt flag.
     pub fn clear_wrapped(&mut self) {
@@ -263,6 +1275,322 @@ impl<'d, T: Channel> Pwm<'d, T> {
     fn bit(&self) -> u32 {
         1 << self.inner.number() as usize
     }
+
+    /// Get a handle that updates the compare value of one output from any context.
+    ///
+    /// The handle does not borrow the driver, so it can be moved into an interrupt handler while
+    /// the [`Pwm`] stays in a task. See [`PwmDutyHandle`] for the guarantees it provides.
+    pub fn duty_handle(&self, output: PwmOutput) -> PwmDutyHandle<'d> {
+        PwmDutyHandle {
+            regs: self.inner.regs(),
+            output,
+            _phantom: PhantomData,
+        }
+    }
+
+    /// Split the PWM driver into independent handles for channel A and channel B.
+    ///
+    /// Both handles share the slice's frequency (`top` and divider) and each controls only its
+    /// own compare value. The slice stays enabled until both handles have been dropped, after
+    /// which it is disabled. Dropping a handle disconnects only its own pin.
+    pub fn split(mut self) -> (ChannelA<'d, T>, ChannelB<'d, T>) {
+        let pin_a = self.pin_a.take();
+        let pin_b = self.pin_b.take();
+        // Safety: `self` is forgotten below, so the two handles are the only owners of the slice.
+        let (inner_a, inner_b) = unsafe { (self.inner.clone_unchecked(), self.inner.clone_unchecked()) };
+        core::mem::forget(self);
+
+        let bits = (1 << inner_a.number()) | (1 << (inner_a.number() + 8));
+        critical_section::with(|cs| {
+            let alive = SPLIT_ALIVE.borrow(cs);
+            alive.set(alive.get() | bits);
+        });
+
+        (
+            ChannelA {
+                inner: inner_a,
+                pin: pin_a,
+            },
+            ChannelB {
+                inner: inner_b,
+                pin: pin_b,
+            },
+        )
+    }
+}
+
+/// Handle updating the compare value of one output of a PWM slice, see [`Pwm::duty_handle`].
+///
+/// The handle only stores the address of the slice's register block, and every access is a
+/// single register operation, so using it from interrupts or the other core is sound:
+///
+/// - Updating the compare value is a read-modify-write of the shared CC register done inside a
+///   critical section, so it never loses a concurrent update of the other output made through
+///   another handle or a split channel.
+/// - Writes made by the [`Pwm`] itself (like [`Pwm::set_config`]) write the whole register at
+///   once, so an update from a handle is either applied before or overwritten by them, never torn.
+/// - The compare register is double-buffered by the hardware, so a new value only takes effect
+///   at the next counter wrap and never produces a glitch within a period.
+///
+/// The handle is valid for the lifetime `'d` of the peripheral. If the [`Pwm`] is dropped
+/// first, writes through the handle only affect the disabled slice.
+#[derive(Clone, Copy)]
+pub struct PwmDutyHandle<'d> {
+    regs: pac::pwm::Channel,
+    output: PwmOutput,
+    _phantom: PhantomData<&'d ()>,
+}
+
+impl<'d> PwmDutyHandle<'d> {
+    /// Set the compare value of the output.
+    #[inline]
+    pub fn set_compare(&self, compare: u16) {
+        critical_section::with(|_| {
+            self.regs.cc().modify(|w| match self.output {
+                PwmOutput::A => w.set_a(compare),
+                PwmOutput::B => w.set_b(compare),
+            })
+        });
+    }
+
+    /// Get the compare value of the output.
+    #[inline]
+    pub fn compare(&self) -> u16 {
+        let cc = self.regs.cc().read();
+        match self.output {
+            PwmOutput::A => cc.a(),
+            PwmOutput::B => cc.b(),
+        }
+    }
+
+    /// Get the `top` value of the slice.
+    #[inline]
+    pub fn top(&self) -> u16 {
+        self.regs.top().read().top()
+    }
+}
+
+/// Complementary outputs on two slices, see [`Pwm::start_complementary`].
+#[derive(Clone, Copy)]
+pub struct ComplementaryPair<'d> {
+    high: PwmDutyHandle<'d>,
+    low: PwmDutyHandle<'d>,
+    dead: u16,
+}
+
+impl<'d> ComplementaryPair<'d> {
+    /// Set the compare value of the high side, clamped to [`ComplementaryPair::max_duty`].
+    ///
+    /// The low side is set `dead` steps above it. The two slices are written one after the
+    /// other, in the order that keeps the dead time if a wrap latches only the first write.
+    pub fn set_duty(&self, compare: u16) {
+        let compare = compare.min(self.max_duty());
+        let low = compare.saturating_add(self.dead);
+        if compare > self.high.compare() {
+            self.low.set_compare(low);
+            self.high.set_compare(compare);
+        } else {
+            self.high.set_compare(compare);
+            self.low.set_compare(low);
+        }
+    }
+
+    /// Get the largest compare value of the high side, `top + 1` minus the dead time in
+    /// counter steps.
+    pub fn max_duty(&self) -> u16 {
+        (self.high.top() as u32 + 1 - self.dead as u32).min(u16::MAX as u32) as u16
+    }
+}
+
+/// Wrap counts of each slice, maintained by [`InterruptHandler`].
+static WRAP_COUNTS: Mutex<Cell<[u64; 8]>> = Mutex::new(Cell::new([0; 8]));
+
+const NEW_AW: AtomicWaker = AtomicWaker::new();
+static WRAP_WAKERS: [AtomicWaker; 8] = [NEW_AW; 8];
+
+/// Interrupt handler counting the wraps of slices started with [`Pwm::start_period_counter`],
+/// and waking the tasks waiting for them, e.g. in [`Pwm::burst`].
+pub struct InterruptHandler {
+    _empty: (),
+}
+
+impl interrupt::typelevel::Handler<interrupt::typelevel::PWM_IRQ_WRAP> for InterruptHandler {
+    unsafe fn on_interrupt() {
+        let ints = pac::PWM.ints().read().0;
+        pac::PWM.intr().write_value(Intr(ints));
+
+        critical_section::with(|cs| {
+            let counts = WRAP_COUNTS.borrow(cs);
+            let mut c = counts.get();
+            for (n, count) in c.iter_mut().enumerate() {
+                if ints & (1 << n) != 0 {
+                    *count += 1;
+                }
+            }
+            counts.set(c);
+        });
+
+        for (n, waker) in WRAP_WAKERS.iter().enumerate() {
+            if ints & (1 << n) != 0 {
+                waker.wake();
+            }
+        }
+    }
+}
+
+/// Alive flags of split slices: bit `n` for channel A and bit `n + 8` for channel B of slice `n`.
+static SPLIT_ALIVE: Mutex<Cell<u16>> = Mutex::new(Cell::new(0));
+
+/// Mark one half of a split slice as dropped, disabling the slice if the other half is gone too.
+fn release_split_half(p: pac::pwm::Channel, number: u8, b: bool) {
+    let bit = 1 << (number + if b { 8 } else { 0 });
+    let both = (1 << number) | (1 << (number + 8));
+    critical_section::with(|cs| {
+        let alive = SPLIT_ALIVE.borrow(cs);
+        let remaining = alive.get() & !bit;
+        alive.set(remaining);
+        if remaining & both == 0 {
+            p.csr().write_clear(|w| w.set_en(true));
+        }
+    });
+}
+
+/// Channel A of a [`Pwm`] slice, obtained from [`Pwm::split`].
+pub struct ChannelA<'d, T: Channel> {
+    inner: PeripheralRef<'d, T>,
+    pin: Option<PeripheralRef<'d, AnyPin>>,
+}
+
+impl<'d, T: Channel> ChannelA<'d, T> {
+    /// Set the compare value of channel A.
+    #[inline]
+    pub fn set_compare(&mut self, compare: u16) {
+        let p = self.inner.regs();
+        critical_section::with(|_| p.cc().modify(|w| w.set_a(compare)));
+    }
+
+    /// Get the compare value of channel A.
+    #[inline]
+    pub fn compare(&self) -> u16 {
+        self.inner.regs().cc().read().a()
+    }
+
+    /// Get the `top` value shared by both channels of the slice.
+    #[inline]
+    pub fn top(&self) -> u16 {
+        self.inner.regs().top().read().top()
+    }
+}
+
+impl<'d, T: Channel> Drop for ChannelA<'d, T> {
+    fn drop(&mut self) {
+        if let Some(pin) = &self.pin {
+            pin.gpio().ctrl().write(|w| w.set_funcsel(31));
+        }
+        release_split_half(self.inner.regs(), self.inner.number(), false);
+    }
+}
+
+/// Channel B of a [`Pwm`] slice, obtained from [`Pwm::split`].
+pub struct ChannelB<'d, T: Channel> {
+    inner: PeripheralRef<'d, T>,
+    pin: Option<PeripheralRef<'d, AnyPin>>,
+}
+
+impl<'d, T: Channel> ChannelB<'d, T> {
+    /// Set the compare value of channel B.
+    #[inline]
+    pub fn set_compare(&mut self, compare: u16) {
+        let p = self.inner.regs();
+        critical_section::with(|_| p.cc().modify(|w| w.set_b(compare)));
+    }
+
+    /// Get the compare value of channel B.
+    #[inline]
+    pub fn compare(&self) -> u16 {
+        self.inner.regs().cc().read().b()
+    }
+
+    /// Get the `top` value shared by both channels of the slice.
+    #[inline]
+    pub fn top(&self) -> u16 {
+        self.inner.regs().top().read().top()
+    }
+}
+
+impl<'d, T: Channel> Drop for ChannelB<'d, T> {
+    fn drop(&mut self) {
+        if let Some(pin) = &self.pin {
+            pin.gpio().ctrl().write(|w| w.set_funcsel(31));
+        }
+        release_split_half(self.inner.regs(), self.inner.number(), true);
+    }
+}
+
+/// Compute the divider and `top` giving `freq_hz` with the largest possible `top`.
+fn timing_for_frequency(freq_hz: u32, phase_correct: bool) -> (FixedU16<fixed::types::extra::U4>, u16) {
+    assert!(freq_hz > 0, "PWM frequency must not be zero");
+
+    // Period in units of 1/16 clock cycles, matching the 4 fractional divider bits.
+    let periods_per_cycle = if phase_correct { 2 } else { 1 };
+    let period16 = clocks::clk_sys_freq() as u64 * 16 / (freq_hz as u64 * periods_per_cycle);
+
+    let div16 = ((period16 + 0xFFFF) / 0x10000).clamp(16, 0xFFF);
+    let top = (period16 / div16).clamp(1, 0x10000) - 1;
+
+    (FixedU16::from_bits(div16 as u16), top as u16)
+}
+
+/// Find the fraction `x / y` closest to `num / den`, with `num <= den`, among those with 16-bit
+/// `x` and `y`, by continued fractions.
+fn pacing_fraction(num: u32, den: u32) -> (u16, u16) {
+    let (mut p0, mut q0, mut p1, mut q1) = (0u64, 1u64, 1u64, 0u64);
+    let (mut n, mut d) = (num as u64, den as u64);
+    while d != 0 {
+        let a = n / d;
+        let (p2, q2) = (a * p1 + p0, a * q1 + q0);
+        if p2 > 0xFFFF || q2 > 0xFFFF {
+            break;
+        }
+        (p0, q0, p1, q1) = (p1, q1, p2, q2);
+        (n, d) = (d, n - a * d);
+    }
+
+    (p1 as u16, q1 as u16)
+}
+
+/// Compute `sin(pi * x)` for `x` in `-0.5..=0.5`, to within 4e-6.
+fn sin_pi(x: f32) -> f32 {
+    let x = x * core::f32::consts::PI;
+    let x2 = x * x;
+    x * (1.0 - x2 / 6.0 * (1.0 - x2 / 20.0 * (1.0 - x2 / 42.0 * (1.0 - x2 / 72.0))))
+}
+
+/// Compute `x^(1/n)` for `x > 0` by bisection, as `core` has no `powf`.
+fn nth_root(x: f32, n: u32) -> f32 {
+    let pow = |mut base: f32| {
+        let mut exp = n;
+        let mut acc = 1.0;
+        while exp > 0 {
+            if exp & 1 != 0 {
+                acc *= base;
+            }
+            base *= base;
+            exp >>= 1;
+        }
+        acc
+    };
+
+    let (mut lo, mut hi) = if x < 1.0 { (x, 1.0) } else { (1.0, x) };
+    for _ in 0..32 {
+        let mid = (lo + hi) / 2.0;
+        if pow(mid) < x {
+            lo = mid;
+        } else {
+            hi = mid;
+        }
+    }
+    (lo + hi) / 2.0
 }
 
 /// Batch representation of PWM channels.
@@ -291,6 +1619,7 @@ impl PwmBatch {
 impl<'d, T: Channel> Drop for Pwm<'d, T> {
     fn drop(&mut self) {
         self.inner.regs().csr().write_clear(|w| w.set_en(false));
+        pac::PWM.inte().write_clear(|w| w.0 = self.bit());
         if let Some(pin) = &self.pin_a {
             pin.gpio().ctrl().write(|w| w.set_funcsel(31));
         }
@@ -376,3 +1705,72 @@ impl_pin!(PIN_26, PWM_CH5, PwmPinA);
 impl_pin!(PIN_27, PWM_CH5, PwmPinB);
 impl_pin!(PIN_28, PWM_CH6, PwmPinA);
 impl_pin!(PIN_29, PWM_CH6, PwmPinB);
+
+// ====================
+
+mod eh02 {
+    use super::*;
+
+    /// Override the pad of a split channel: `Some(inverted)` forces the inactive level, `None`
+    /// gives the pin back to the PWM.
+    fn force_inactive(pin: &Option<PeripheralRef<'_, AnyPin>>, inverted: Option<bool>) {
+        if let Some(pin) = pin {
+            let level = match inverted {
+                None => Outover::NORMAL,
+                Some(false) => Outover::LOW,
+                Some(true) => Outover::HIGH,
+            };
+            pin.gpio().ctrl().modify(|w| w.set_outover(level));
+        }
+    }
+
+    impl<'d, T: Channel> embedded_hal_02::PwmPin for ChannelA<'d, T> {
+        type Duty = u16;
+
+        fn disable(&mut self) {
+            force_inactive(&self.pin, Some(self.inner.regs().csr().read().a_inv()));
+        }
+
+        fn enable(&mut self) {
+            force_inactive(&self.pin, None);
+            self.inner.regs().csr().write_set(|w| w.set_en(true));
+        }
+
+        fn get_duty(&self) -> Self::Duty {
+            self.compare()
+        }
+
+        fn get_max_duty(&self) -> Self::Duty {
+            self.top().saturating_add(1)
+        }
+
+        fn set_duty(&mut self, duty: Self::Duty) {
+            self.set_compare(duty)
+        }
+    }
+
+    impl<'d, T: Channel> embedded_hal_02::PwmPin for ChannelB<'d, T> {
+        type Duty = u16;
+
+        fn disable(&mut self) {
+            force_inactive(&self.pin, Some(self.inner.regs().csr().read().b_inv()));
+        }
+
+        fn enable(&mut self) {
+            force_inactive(&self.pin, None);
+            self.inner.regs().csr().write_set(|w| w.set_en(true));
+        }
+
+        fn get_duty(&self) -> Self::Duty {
+            self.compare()
+        }
+
+        fn get_max_duty(&self) -> Self::Duty {
+            self.top().saturating_add(1)
+        }
+
+        fn set_duty(&mut self, duty: Self::Duty) {
+            self.set_compare(duty)
+        }
+    }
+}
@@ -17,7 +17,7 @@ mod _version;
 #[allow(unused)]
 #[cfg(not(adc_f3_v2))]
 pub use _version::*;
-#[cfg(any(adc_f1, adc_f3, adc_v1, adc_l0, adc_f3_v1_1))]
+#[cfg(any(adc_f1, adc_f3, adc_v1, adc_l0, adc_f3_v1_1, adc_v4))]
 use embassy_sync::waitqueue::AtomicWaker;
 
 #[cfg(not(any(adc_f1, adc_f3_v2)))]
@@ -25,12 +25,44 @@ pub use crate::pac::adc::vals::Res as Resolution;
 pub use crate::pac::adc::vals::SampleTime;
 use crate::peripherals;
 
+/// ADC error.
+#[cfg(adc_v4)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Error {
+    /// The operation did not complete in time.
+    Timeout,
+    /// The ADC is not enabled or not ready for the requested operation.
+    NotReady,
+    /// A conversion result was overwritten before it was read.
+    Overrun,
+    /// The conversion time doesn't fit in the trigger period, so triggers would be missed.
+    ///
+    /// This is returned when arming triggered conversions, missed triggers are not detected at
+    /// runtime.
+    TriggerOverrun,
+    /// The ADC kernel clock exceeds the maximum allowed frequency, even with the largest usable prescaler.
+    ClockTooHigh,
+    /// All hardware offset registers are already assigned to other channels.
+    OffsetSlotsFull,
+    /// The requested configuration is not supported by this ADC.
+    InvalidConfig,
+    /// A reading was outside its expected range.
+    OutOfRange,
+}
+
 /// Analog to Digital driver.
 pub struct Adc<'d, T: Instance> {
     #[allow(unused)]
     adc: crate::PeripheralRef<'d, T>,
     #[cfg(not(any(adc_f3_v2, adc_f3_v1_1)))]
     sample_time: SampleTime,
+    #[cfg(adc_v4)]
+    injected_len: u8,
+    #[cfg(adc_v4)]
+    prepared_channel: Option<u8>,
+    #[cfg(adc_v4)]
+    clock: crate::time::Hertz,
 }
 
 #[cfg(any(adc_f1, adc_f3, adc_v1, adc_l0, adc_f3_v1_1))]
@@ -47,12 +79,28 @@ impl State {
     }
 }
 
+#[cfg(adc_v4)]
+pub struct State {
+    pub waker: AtomicWaker,
+    pub on_sample: critical_section::Mutex<core::cell::Cell<Option<fn(u16)>>>,
+}
+
+#[cfg(adc_v4)]
+impl State {
+    pub const fn new() -> Self {
+        Self {
+            waker: AtomicWaker::new(),
+            on_sample: critical_section::Mutex::new(core::cell::Cell::new(None)),
+        }
+    }
+}
+
 trait SealedInstance {
     #[allow(unused)]
     fn regs() -> crate::pac::adc::Adc;
     #[cfg(not(any(adc_f1, adc_v1, adc_l0, adc_f3_v2, adc_f3_v1_1, adc_g0)))]
     fn common_regs() -> crate::pac::adccommon::AdcCommon;
-    #[cfg(any(adc_f1, adc_f3, adc_v1, adc_l0, adc_f3_v1_1))]
+    #[cfg(any(adc_f1, adc_f3, adc_v1, adc_l0, adc_f3_v1_1, adc_v4))]
     fn state() -> &'static State;
 }
 
@@ -69,6 +117,13 @@ trait SealedInternalChannel<T> {
     fn channel(&self) -> u8;
 }
 
+#[cfg(adc_v4)]
+trait SealedAdcChannel<T> {
+    fn setup(&mut self) {}
+
+    fn channel(&self) -> u8;
+}
+
 /// ADC instance.
 #[cfg(not(any(adc_f1, adc_v1, adc_l0, adc_v2, adc_v3, adc_v4, adc_f3, adc_f3_v1_1, adc_g0, adc_h5)))]
 #[allow(private_bounds)]
@@ -88,6 +143,10 @@ pub trait AdcPin<T: Instance>: SealedAdcPin<T> {}
 /// ADC internal channel.
 #[allow(private_bounds)]
 pub trait InternalChannel<T>: SealedInternalChannel<T> {}
+/// ADC channel, either an [`AdcPin`] or an [`InternalChannel`].
+#[cfg(adc_v4)]
+#[allow(private_bounds)]
+pub trait AdcChannel<T>: SealedAdcChannel<T> {}
 
 foreach_adc!(
     ($inst:ident, $common_inst:ident, $clock:ident) => {
@@ -101,7 +160,7 @@ foreach_adc!(
                 return crate::pac::$common_inst
             }
 
-            #[cfg(any(adc_f1, adc_f3, adc_v1, adc_l0, adc_f3_v1_1))]
+            #[cfg(any(adc_f1, adc_f3, adc_v1, adc_l0, adc_f3_v1_1, adc_v4))]
             fn state() -> &'static State {
                 static STATE: State = State::new();
                 &STATE
@@ -128,6 +187,20 @@ macro_rules! impl_adc_pin {
                 $ch
             }
         }
+
+        #[cfg(adc_v4)]
+        impl crate::adc::AdcChannel<peripherals::$inst> for crate::peripherals::$pin {}
+
+        #[cfg(adc_v4)]
+        impl crate::adc::SealedAdcChannel<peripherals::$inst> for crate::peripherals::$pin {
+            fn setup(&mut self) {
+                <Self as crate::gpio::SealedPin>::set_as_analog(self);
+            }
+
+            fn channel(&self) -> u8 {
+                $ch
+            }
+        }
     };
 }
 
@@ -1,11 +1,14 @@
+use core::marker::PhantomData;
+
 use embedded_hal_02::blocking::delay::DelayUs;
 #[allow(unused)]
-use pac::adc::vals::{Adcaldif, Boost, Difsel, Exten, Pcsel};
-use pac::adccommon::vals::Presc;
+use pac::adc::vals::{Adcaldif, Adstp, Boost, Difsel, Dmngt, Exten, Jexten, Pcsel};
+use pac::adccommon::vals::{Dual, Presc};
 
-use super::{Adc, AdcPin, Instance, InternalChannel, Resolution, SampleTime};
+use super::{Adc, AdcChannel, AdcPin, Error, Instance, InternalChannel, Resolution, SampleTime};
+use crate::interrupt::typelevel::Interrupt;
 use crate::time::Hertz;
-use crate::{pac, Peripheral};
+use crate::{interrupt, pac, Peripheral};
 
 /// Default VREF voltage used for sample conversion to millivolts.
 pub const VREF_DEFAULT_MV: u32 = 3300;
@@ -18,6 +21,14 @@ const MAX_ADC_CLK_FREQ: Hertz = Hertz::mhz(60);
 #[cfg(stm32h7)]
 const MAX_ADC_CLK_FREQ: Hertz = Hertz::mhz(50);
 
+// Factory calibration values in system memory: (VREFINT_CAL, TS_CAL1, TS_CAL2).
+#[cfg(stm32g4)]
+const CALIBRATION_ADDRS: (usize, usize, usize) = (0x1FFF_75AA, 0x1FFF_75A8, 0x1FFF_75CA);
+#[cfg(all(stm32h7, not(pwr_h7rm0455)))]
+const CALIBRATION_ADDRS: (usize, usize, usize) = (0x1FF1_E860, 0x1FF1_E820, 0x1FF1_E840);
+#[cfg(all(stm32h7, pwr_h7rm0455))]
+const CALIBRATION_ADDRS: (usize, usize, usize) = (0x08FF_F810, 0x08FF_F814, 0x08FF_F818);
+
 #[cfg(stm32g4)]
 const VREF_CHANNEL: u8 = 18;
 #[cfg(stm32g4)]
@@ -28,10 +39,72 @@ const VREF_CHANNEL: u8 = 19;
 #[cfg(stm32h7)]
 const TEMP_CHANNEL: u8 = 18;
 
-// TODO this should be 14 for H7a/b/35
+// VBAT is on ADC3 channel 17 on H74x/H75x and H72x/H73x (RM0433, RM0468), and on ADC2
+// channel 14 on H7Ax/H7Bx (RM0455), see `Adc::has_internal`.
+#[cfg(not(any(stm32h7ax, stm32h7bx)))]
 const VBAT_CHANNEL: u8 = 17;
+#[cfg(any(stm32h7ax, stm32h7bx))]
+const VBAT_CHANNEL: u8 = 14;
+
+// Conditions of the factory calibration: (max count, VDDA in mV, TS_CAL2 temperature in °C).
+#[cfg(stm32g4)]
+const CALIBRATION_CONDITIONS: (u32, u32, i32) = (4095, 3000, 130);
+#[cfg(stm32h7)]
+const CALIBRATION_CONDITIONS: (u32, u32, i32) = (65535, 3300, 110);
+
+/// Ratio of the internal bridge divider in front of the VBAT channel.
+#[cfg(stm32g4)]
+const VBAT_DIVIDER: u32 = 3;
+#[cfg(stm32h7)]
+const VBAT_DIVIDER: u32 = 4;
+
+/// Number of hardware offset (OFR) registers.
+const OFFSET_SLOTS: usize = 4;
+
+/// Interrupt handler waking [`Adc::read_timeout`] and calling the callback registered with
+/// [`Adc::on_sample`].
+pub struct InterruptHandler<T: Instance> {
+    _phantom: PhantomData<T>,
+}
+
+impl<T: Instance> interrupt::typelevel::Handler<T::Interrupt> for InterruptHandler<T> {
+    unsafe fn on_interrupt() {
+        // The interrupt line may be shared with another ADC.
+        let ier = T::regs().ier().read();
+        let isr = T::regs().isr().read();
+
+        if ier.eosie() && isr.eos() {
+            T::regs().ier().modify(|w| w.set_eosie(false));
+            T::state().waker.wake();
+        }
+
+        if !ier.eocie() || !isr.eoc() {
+            return;
+        }
+
+        // EOC is cleared by reading DR.
+        let sample = T::regs().dr().read().0 as u16;
+        if let Some(on_sample) = critical_section::with(|cs| T::state().on_sample.borrow(cs).get()) {
+            on_sample(sample);
+        }
+    }
+}
 
 // NOTE: Vrefint/Temperature/Vbat are not available on all ADCs, this currently cannot be modeled with stm32-data, so these are available from the software on all ADCs
+// and `Adc::has_internal` reports the actual availability.
+
+/// Internal channel source, see [`Adc::has_internal`].
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum InternalSource {
+    /// Internal voltage reference, see [`VrefInt`].
+    VrefInt,
+    /// Temperature sensor, see [`Temperature`].
+    Temperature,
+    /// Battery voltage, see [`Vbat`].
+    Vbat,
+}
+
 /// Internal voltage reference channel.
 pub struct VrefInt;
 impl<T: Instance> InternalChannel<T> for VrefInt {}
@@ -40,6 +113,12 @@ impl<T: Instance> super::SealedInternalChannel<T> for VrefInt {
         VREF_CHANNEL
     }
 }
+impl<T: Instance> AdcChannel<T> for VrefInt {}
+impl<T: Instance> super::SealedAdcChannel<T> for VrefInt {
+    fn channel(&self) -> u8 {
+        VREF_CHANNEL
+    }
+}
 
 /// Internal temperature channel.
 pub struct Temperature;
@@ -49,6 +128,12 @@ impl<T: Instance> super::SealedInternalChannel<T> for Temperature {
         TEMP_CHANNEL
     }
 }
+impl<T: Instance> AdcChannel<T> for Temperature {}
+impl<T: Instance> super::SealedAdcChannel<T> for Temperature {
+    fn channel(&self) -> u8 {
+        TEMP_CHANNEL
+    }
+}
 
 /// Internal battery voltage channel.
 pub struct Vbat;
@@ -58,6 +143,12 @@ impl<T: Instance> super::SealedInternalChannel<T> for Vbat {
         VBAT_CHANNEL
     }
 }
+impl<T: Instance> AdcChannel<T> for Vbat {}
+impl<T: Instance> super::SealedAdcChannel<T> for Vbat {
+    fn channel(&self) -> u8 {
+        VBAT_CHANNEL
+    }
+}
 
 // NOTE (unused): The prescaler enum closely copies the hardware capabilities,
 // but high prescaling doesn't make a lot of sense in the current implementation and is ommited.
@@ -78,17 +169,17 @@ enum Prescaler {
 }
 
 impl Prescaler {
-    fn from_ker_ck(frequency: Hertz) -> Self {
+    fn from_ker_ck(frequency: Hertz) -> Option<Self> {
         let raw_prescaler = frequency.0 / MAX_ADC_CLK_FREQ.0;
         match raw_prescaler {
-            0 => Self::NotDivided,
-            1 => Self::DividedBy2,
-            2..=3 => Self::DividedBy4,
-            4..=5 => Self::DividedBy6,
-            6..=7 => Self::DividedBy8,
-            8..=9 => Self::DividedBy10,
-            10..=11 => Self::DividedBy12,
-            _ => unimplemented!(),
+            0 => Some(Self::NotDivided),
+            1 => Some(Self::DividedBy2),
+            2..=3 => Some(Self::DividedBy4),
+            4..=5 => Some(Self::DividedBy6),
+            6..=7 => Some(Self::DividedBy8),
+            8..=9 => Some(Self::DividedBy10),
+            10..=11 => Some(Self::DividedBy12),
+            _ => None,
         }
     }
 
@@ -127,13 +218,226 @@ impl Prescaler {
     }
 }
 
+/// Routing of regular conversion data.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum DataManagement {
+    /// Results are only stored in the data register, DMA requests are disabled.
+    Register,
+    /// DMA requests are generated until the DMA transfer count is reached, then stop.
+    DmaOneShot,
+    /// DMA requests are generated for every conversion, for use with a circular DMA buffer.
+    DmaCircular,
+    /// Results are routed to the DFSDM.
+    #[cfg(stm32h7)]
+    Dfsdm,
+}
+
+impl From<DataManagement> for Dmngt {
+    fn from(value: DataManagement) -> Self {
+        // On G4 the same two bits are DMAEN and DMACFG, which gives the same encoding for the
+        // register-only and DMA modes.
+        match value {
+            DataManagement::Register => Dmngt::DR,
+            DataManagement::DmaOneShot => Dmngt::DMA_ONESHOT,
+            DataManagement::DmaCircular => Dmngt::DMA_CIRCULAR,
+            #[cfg(stm32h7)]
+            DataManagement::Dfsdm => Dmngt::DFSDM,
+        }
+    }
+}
+
+/// ADC configuration applied as a whole with [`Adc::apply_config`].
+#[non_exhaustive]
+#[derive(Clone, Copy)]
+pub struct AdcConfig {
+    /// Conversion resolution.
+    pub resolution: Resolution,
+    /// Sample time used for every channel.
+    pub sample_time: SampleTime,
+    /// Routing of regular conversion data.
+    pub data_management: DataManagement,
+}
+
+impl Default for AdcConfig {
+    fn default() -> Self {
+        Self {
+            resolution: Resolution::BITS12,
+            sample_time: SampleTime::from_bits(0),
+            data_management: DataManagement::Register,
+        }
+    }
+}
+
+/// Two point gain and offset correction of a channel, see [`Adc::blocking_read_calibrated`].
+///
+/// Applied as `mv = low_mv + (raw - low_raw) * slope / 2^16`, with the slope in 16.16 fixed
+/// point. The value is owned by the caller, e.g. stored next to the channel it was measured on.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct TwoPointCalibration {
+    low_raw: i32,
+    low_mv: i32,
+    slope: i64,
+}
+
+impl TwoPointCalibration {
+    /// Compute the correction from two reference points.
+    ///
+    /// `low_raw` and `high_raw` are the raw readings measured with the known `low_ref_mv` and
+    /// `high_ref_mv` applied to the channel, at the resolution that will be used afterwards. This
+    /// corrects the gain and offset errors of the whole signal chain, on top of the ADC's own
+    /// calibration and any hardware offset, which are applied to the raw result first. Returns
+    /// [`Error::InvalidConfig`] if the two readings are equal.
+    pub fn new(low_ref_mv: u16, high_ref_mv: u16, low_raw: u16, high_raw: u16) -> Result<Self, Error> {
+        if low_raw == high_raw {
+            return Err(Error::InvalidConfig);
+        }
+
+        let slope = ((high_ref_mv as i64 - low_ref_mv as i64) << 16) / (high_raw as i64 - low_raw as i64);
+        Ok(Self {
+            low_raw: low_raw as i32,
+            low_mv: low_ref_mv as i32,
+            slope,
+        })
+    }
+
+    /// Convert a raw reading to millivolts, saturating at 0 and `u16::MAX`.
+    pub fn to_millivolts(&self, raw: u16) -> u16 {
+        let mv = self.low_mv as i64 + (((raw as i32 - self.low_raw) as i64 * self.slope) >> 16);
+        mv.clamp(0, u16::MAX as i64) as u16
+    }
+}
+
+/// State of the moving average filter of one channel, see [`Adc::blocking_read_filtered`].
+#[derive(Clone, Copy, Default, PartialEq, Eq, Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct FilterState(Option<u32>);
+
+impl FilterState {
+    /// Create an empty filter, which starts from the first sample.
+    pub const fn new() -> Self {
+        Self(None)
+    }
+
+    /// Reset the filter, so the next read starts it again from the sample.
+    pub fn reset(&mut self) {
+        self.0 = None;
+    }
+}
+
+/// Analog watchdog status flags.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct WatchdogFlags {
+    /// Analog watchdog 1 detected a conversion outside its window.
+    pub awd1: bool,
+    /// Analog watchdog 2 detected a conversion outside its window.
+    pub awd2: bool,
+    /// Analog watchdog 3 detected a conversion outside its window.
+    pub awd3: bool,
+}
+
+/// Per-unit analog calibration values, see [`Adc::factory_calibration`].
+///
+/// This is a snapshot of read-only factory data and of the ADC calibration factor, changing
+/// it has no effect on the hardware.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct CalibrationData {
+    /// VREFINT_CAL, the raw 12-bit (G4) or 16-bit (H7) VREFINT reading at VDDA = 3.0 V (G4) or
+    /// 3.3 V (H7) and 30 °C.
+    pub vrefint_cal: u16,
+    /// TS_CAL1, the raw temperature sensor reading at 30 °C.
+    pub ts_cal1: u16,
+    /// TS_CAL2, the raw temperature sensor reading at 130 °C (G4) or 110 °C (H7).
+    pub ts_cal2: u16,
+    /// The single-ended calibration factor of this ADC, see [`Adc::calibration_factor`].
+    pub calfact: u16,
+}
+
+trait SealedTriggerTimer {
+    /// EXTSEL value selecting the TRGO of this timer, the same on every ADC of the family.
+    const EXTSEL: u8;
+}
+
+/// Timer whose TRGO can trigger regular conversions, see [`Adc::link_timer_trigger`].
+#[allow(private_bounds)]
+pub trait TriggerTimer: SealedTriggerTimer + crate::timer::BasicInstance {}
+
+macro_rules! impl_trigger_timer {
+    ($($(#[$cfg:meta])* $inst:ident => $extsel:expr,)*) => {
+        $(
+            $(#[$cfg])*
+            impl SealedTriggerTimer for crate::peripherals::$inst {
+                const EXTSEL: u8 = $extsel;
+            }
+            $(#[$cfg])*
+            impl TriggerTimer for crate::peripherals::$inst {}
+        )*
+    };
+}
+
+// RM0440 and RM0433, regular external trigger selection. The TRGO inputs have the same EXTSEL
+// values on G4 and H7, TIM7 and TIM20 are G4 only.
+impl_trigger_timer! {
+    #[cfg(peri_tim1)] TIM1 => 9,
+    #[cfg(peri_tim2)] TIM2 => 11,
+    #[cfg(peri_tim3)] TIM3 => 4,
+    #[cfg(peri_tim4)] TIM4 => 12,
+    #[cfg(peri_tim6)] TIM6 => 13,
+    #[cfg(all(stm32g4, peri_tim7))] TIM7 => 30,
+    #[cfg(peri_tim8)] TIM8 => 7,
+    #[cfg(peri_tim15)] TIM15 => 14,
+    #[cfg(all(stm32g4, peri_tim20))] TIM20 => 16,
+}
+
+/// Regular and injected results of one trigger, see [`Adc::blocking_read_frame`].
+#[cfg(feature = "time")]
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct AdcFrame<const N: usize> {
+    /// Estimated instant of the trigger that started the frame.
+    pub timestamp: embassy_time::Instant,
+    /// Regular results, in the order the channels were passed.
+    pub regular: [u16; N],
+    /// Injected results by rank, as read from JDR1 to JDR4. Ranks beyond the injected
+    /// sequence length read as 0.
+    pub injected: [u16; 4],
+}
+
+/// Calibrated internal channel readings, see [`Adc::read_system`].
+#[derive(Clone, Copy, PartialEq, Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct SystemMeasurements {
+    /// Analog supply voltage in millivolts, derived from VREFINT and its factory calibration.
+    pub vdda_mv: u16,
+    /// Die temperature in °C, from the temperature sensor and its two factory calibration points.
+    pub temperature_c: f32,
+    /// Battery voltage in millivolts, corrected for the VBAT divider and the measured VDDA.
+    pub vbat_mv: u16,
+}
+
 impl<'d, T: Instance> Adc<'d, T> {
     /// Create a new ADC driver.
+    ///
+    /// Panics if the ADC clock is too high, see [`Adc::try_new`] for a fallible version.
     pub fn new(adc: impl Peripheral<P = T> + 'd, delay: &mut impl DelayUs<u16>) -> Self {
+        match Self::try_new(adc, delay) {
+            Ok(s) => s,
+            Err(_) => panic!("Maximal allowed frequency for the ADC is {} MHz and it varies with different packages, refer to ST docs for more information.", MAX_ADC_CLK_FREQ.0 /  1_000_000 ),
+        }
+    }
+
+    /// Create a new ADC driver, returning an error instead of panicking.
+    ///
+    /// Returns [`Error::ClockTooHigh`] if the ADC kernel clock cannot be divided below the
+    /// maximum allowed ADC clock frequency.
+    pub fn try_new(adc: impl Peripheral<P = T> + 'd, delay: &mut impl DelayUs<u16>) -> Result<Self, Error> {
         embassy_hal_internal::into_ref!(adc);
         T::enable_and_reset();
 
-        let prescaler = Prescaler::from_ker_ck(T::frequency());
+        let prescaler = Prescaler::from_ker_ck(T::frequency()).ok_or(Error::ClockTooHigh)?;
 
         T::common_regs().ccr().modify(|w| w.set_presc(prescaler.presc()));
 
@@ -141,7 +445,7 @@ impl<'d, T: Instance> Adc<'d, T> {
         info!("ADC frequency set to {} Hz", frequency.0);
 
         if frequency > MAX_ADC_CLK_FREQ {
-            panic!("Maximal allowed frequency for the ADC is {} MHz and it varies with different packages, refer to ST docs for more information.", MAX_ADC_CLK_FREQ.0 /  1_000_000 );
+            return Err(Error::ClockTooHigh);
         }
 
         #[cfg(stm32h7)]
@@ -160,6 +464,9 @@ impl<'d, T: Instance> Adc<'d, T> {
         let mut s = Self {
             adc,
             sample_time: SampleTime::from_bits(0),
+            injected_len: 0,
+            prepared_channel: None,
+            clock: frequency,
         };
         s.power_up(delay);
         s.configure_differential_inputs();
@@ -170,7 +477,7 @@ impl<'d, T: Instance> Adc<'d, T> {
         s.enable();
         s.configure();
 
-        s
+        Ok(s)
     }
 
     fn power_up(&mut self, delay: &mut impl DelayUs<u16>) {
@@ -201,6 +508,48 @@ impl<'d, T: Instance> Adc<'d, T> {
         while T::regs().cr().read().adcal() {}
     }
 
+    /// Read the single-ended calibration factor computed by the last calibration.
+    ///
+    /// Store this value to restore it with [`Adc::set_calibration_factor`] after waking from a low
+    /// power mode that resets the ADC, skipping a full recalibration.
+    pub fn calibration_factor(&self) -> u16 {
+        T::regs().calfact().read().calfact_s()
+    }
+
+    /// Read the factory calibration values of this unit together with the ADC calibration factor.
+    ///
+    /// The factory values are read from system memory: VREFINT_CAL, TS_CAL1 and TS_CAL2 are at
+    /// 0x1FFF75AA, 0x1FFF75A8 and 0x1FFF75CA on G4, at 0x1FF1E860, 0x1FF1E820 and 0x1FF1E840 on
+    /// H742/743/750/753 and H72x/73x, and at 0x08FFF810, 0x08FFF814 and 0x08FFF818 on H7A3/B0/B3.
+    pub fn factory_calibration(&self) -> CalibrationData {
+        let (vrefint_cal, ts_cal1, ts_cal2) = CALIBRATION_ADDRS;
+        // Safety: these are valid, aligned addresses of read-only factory data.
+        let read = |addr: usize| unsafe { core::ptr::read_volatile(addr as *const u16) };
+
+        CalibrationData {
+            vrefint_cal: read(vrefint_cal),
+            ts_cal1: read(ts_cal1),
+            ts_cal2: read(ts_cal2),
+            calfact: self.calibration_factor(),
+        }
+    }
+
+    /// Write the single-ended calibration factor.
+    ///
+    /// The calibration factor can only be written while the ADC is enabled (ADEN=1) and no regular
+    /// or injected conversion is ongoing (ADSTART=0, JADSTART=0), which is the case between reads.
+    /// Returns [`Error::NotReady`] otherwise, without writing the factor.
+    pub fn set_calibration_factor(&mut self, factor: u16) -> Result<(), Error> {
+        let cr = T::regs().cr().read();
+        if !cr.aden() || cr.adstart() || cr.jadstart() {
+            return Err(Error::NotReady);
+        }
+
+        T::regs().calfact().modify(|w| w.set_calfact_s(factor));
+
+        Ok(())
+    }
+
     fn enable(&mut self) {
         T::regs().isr().write(|w| w.set_adrdy(true));
         T::regs().cr().modify(|w| w.set_aden(true));
@@ -243,21 +592,661 @@ impl<'d, T: Instance> Adc<'d, T> {
         Vbat {}
     }
 
+    /// Check whether `source` is connected to this ADC at the channel number this driver uses.
+    ///
+    /// The internal channels are wired to specific ADCs:
+    /// - G4: VREFINT on every ADC but ADC2, VBAT on ADC1, ADC3 and ADC5, and the temperature
+    ///   sensor on ADC1. ADC5 also has the temperature sensor, but on channel 4, which this
+    ///   driver doesn't use.
+    /// - H74x/H75x and H72x/H73x: all three on ADC3.
+    /// - H7Ax/H7Bx: all three on ADC2.
+    ///
+    /// Reading a source on an ADC without it returns an unrelated input.
+    pub fn has_internal(source: InternalSource) -> bool {
+        let adc = Self::instance_number();
+
+        #[cfg(stm32g4)]
+        return match source {
+            InternalSource::VrefInt => adc != 2,
+            InternalSource::Temperature => adc == 1,
+            InternalSource::Vbat => matches!(adc, 1 | 3 | 5),
+        };
+
+        #[cfg(stm32h7)]
+        {
+            let _ = source;
+            #[cfg(any(stm32h7ax, stm32h7bx))]
+            return adc == 2;
+            #[cfg(not(any(stm32h7ax, stm32h7bx)))]
+            return adc == 3;
+        }
+    }
+
+    /// Get the number of this ADC, e.g. 3 for ADC3.
+    fn instance_number() -> u8 {
+        #[allow(unused)]
+        let is = |adc: pac::adc::Adc| T::regs().as_ptr() == adc.as_ptr();
+        #[cfg(peri_adc2)]
+        if is(pac::ADC2) {
+            return 2;
+        }
+        #[cfg(peri_adc3)]
+        if is(pac::ADC3) {
+            return 3;
+        }
+        #[cfg(peri_adc4)]
+        if is(pac::ADC4) {
+            return 4;
+        }
+        #[cfg(peri_adc5)]
+        if is(pac::ADC5) {
+            return 5;
+        }
+        1
+    }
+
+    /// Read the battery voltage in millivolts.
+    ///
+    /// The VBAT channel is enabled, read with the longest sample time, and disabled again unless
+    /// it was already enabled, as its bridge divider draws current from the battery while
+    /// enabled. The result is corrected for the divider, VBAT/3 on G4 and VBAT/4 on H7, assuming
+    /// VDDA is [`VREF_DEFAULT_MV`].
+    pub fn blocking_read_vbat_millivolts(&mut self) -> u16 {
+        let raw = self.read_vbat_raw();
+        let max = super::resolution_to_max_count(self.resolution());
+        (raw * VREF_DEFAULT_MV * VBAT_DIVIDER / max) as u16
+    }
+
+    /// Read the VBAT channel with the longest sample time, disabling it afterwards unless it was
+    /// already enabled.
+    fn read_vbat_raw(&mut self) -> u32 {
+        let vbaten = T::common_regs().ccr().read().vbaten();
+        let mut vbat = self.enable_vbat();
+
+        let sample_time = self.sample_time;
+        self.set_sample_time(SampleTime::from_bits(7));
+        let raw = self.blocking_read(&mut vbat) as u32;
+        self.set_sample_time(sample_time);
+
+        T::common_regs().ccr().modify(|reg| reg.set_vbaten(vbaten));
+        raw
+    }
+
+    /// Check that VREFINT reads as expected for a VDDA of [`VREF_DEFAULT_MV`].
+    ///
+    /// VREFINT is read with the longest sample time, leaving VREFEN as it was, and compared with
+    /// the reading expected from VREFINT_CAL, scaled to the assumed VDDA and the current
+    /// resolution. A deviation of more than 10% returns [`Error::OutOfRange`], which covers the
+    /// VREFINT spread over temperature and a VDDA within about ±10% of the assumed value, so a
+    /// failure points to a supply out of spec, e.g. a brown-out, or a misconfigured ADC. Returns
+    /// [`Error::InvalidConfig`] if this ADC has no VREFINT channel, see [`Adc::has_internal`].
+    pub fn self_check(&mut self) -> Result<(), Error> {
+        if !Self::has_internal(InternalSource::VrefInt) {
+            return Err(Error::InvalidConfig);
+        }
+
+        let vrefen = T::common_regs().ccr().read().vrefen();
+        let mut vrefint = self.enable_vrefint();
+        let sample_time = self.sample_time;
+        self.set_sample_time(SampleTime::from_bits(7));
+        let raw = self.blocking_read(&mut vrefint) as u32;
+        self.set_sample_time(sample_time);
+        T::common_regs().ccr().modify(|reg| reg.set_vrefen(vrefen));
+
+        let (cal_max, cal_mv, _) = CALIBRATION_CONDITIONS;
+        let max = super::resolution_to_max_count(self.resolution());
+        let cal = self.factory_calibration().vrefint_cal as u64;
+        let expected = cal * cal_mv as u64 * max as u64 / (VREF_DEFAULT_MV as u64 * cal_max as u64);
+
+        let deviation = (raw as u64).abs_diff(expected);
+        if deviation * 10 > expected {
+            return Err(Error::OutOfRange);
+        }
+        Ok(())
+    }
+
+    /// Read the supply voltage, die temperature and battery voltage in one call.
+    ///
+    /// VREFINT, the temperature sensor and VBAT are briefly enabled, read with the longest
+    /// sample time, and disabled again unless they were already enabled. VDDA is derived from
+    /// VREFINT and VREFINT_CAL, and then used to scale the temperature and VBAT readings, so the
+    /// results don't depend on the supply being exactly [`VREF_DEFAULT_MV`]. The three
+    /// conversions take about 3 × 660 ADC clock cycles on G4 and 3 × 820 on H7, e.g. about 33 µs
+    /// or 50 µs at the maximum ADC clock. Returns [`Error::Overrun`] if a result was overwritten
+    /// before it could be read.
+    pub fn read_system(&mut self) -> Result<SystemMeasurements, Error> {
+        let ccr = T::common_regs().ccr().read();
+        let sequence = self.read_vref_and_temperature();
+        T::common_regs().ccr().modify(|reg| {
+            reg.set_vrefen(ccr.vrefen());
+            reg.set_vsenseen(ccr.vsenseen());
+        });
+        let (vref_raw, temp_raw) = sequence?;
+        let vbat_raw = self.read_vbat_raw();
+
+        let (cal_max, cal_mv, ts_cal2_c) = CALIBRATION_CONDITIONS;
+        let max = super::resolution_to_max_count(self.resolution());
+        let cal = self.factory_calibration();
+
+        // Scale a raw sample to the resolution of the factory calibration.
+        let to_cal = |raw: u16| raw as u32 * cal_max / max;
+        let vdda_mv = cal_mv * cal.vrefint_cal as u32 / to_cal(vref_raw).max(1);
+
+        // The temperature sensor was calibrated at `cal_mv`, rescale to that supply.
+        let ts = (to_cal(temp_raw) * vdda_mv / cal_mv) as f32;
+        let (ts_cal1, ts_cal2) = (cal.ts_cal1 as f32, cal.ts_cal2 as f32);
+        let temperature_c = 30.0 + (ts - ts_cal1) * (ts_cal2_c - 30) as f32 / (ts_cal2 - ts_cal1);
+
+        Ok(SystemMeasurements {
+            vdda_mv: vdda_mv as u16,
+            temperature_c,
+            vbat_mv: (vbat_raw * vdda_mv * VBAT_DIVIDER / max) as u16,
+        })
+    }
+
+    /// Read the internal voltage reference and the temperature sensor in a single sequence.
+    ///
+    /// Both internal channels are enabled and converted back to back as a two channel regular
+    /// sequence, minimizing the time skew between them. The longest sample time is used, which
+    /// satisfies the minimum sampling time of both internal channels at any allowed ADC clock.
+    /// Returns `(vrefint, temperature)` raw samples, or [`Error::Overrun`] if a result was
+    /// overwritten before it could be read.
+    pub fn read_vref_and_temperature(&mut self) -> Result<(u16, u16), Error> {
+        self.enable_vrefint();
+        self.enable_temperature();
+
+        self.prepared_channel = None;
+        let sample_time = SampleTime::from_bits(7);
+        Self::set_channel_sample_time(VREF_CHANNEL, sample_time);
+        Self::set_channel_sample_time(TEMP_CHANNEL, sample_time);
+
+        let mut results = [0; 2];
+        self.convert_sequence(&[VREF_CHANNEL, TEMP_CHANNEL], &mut results)?;
+
+        Ok((results[0], results[1]))
+    }
+
     /// Set the ADC sample time.
     pub fn set_sample_time(&mut self, sample_time: SampleTime) {
         self.sample_time = sample_time;
+        self.prepared_channel = None;
+    }
+
+    /// Set the sample time of all channels at once.
+    ///
+    /// Unlike [`Adc::set_sample_time`], which is applied to a channel when it is read, this
+    /// immediately writes both SMPR registers, so a following sequence or DMA burst uses the
+    /// same sample time for every channel without touching SMPR per channel.
+    pub fn set_uniform_sample_time(&mut self, sample_time: SampleTime) {
+        self.sample_time = sample_time;
+        self.prepared_channel = None;
+
+        for n in 0..2 {
+            T::regs().smpr(n).write(|w| {
+                for ch in 0..10 {
+                    w.set_smp(ch, sample_time);
+                }
+            });
+        }
     }
 
     /// Set the ADC resolution.
     pub fn set_resolution(&mut self, resolution: Resolution) {
+        self.prepared_channel = None;
         T::regs().cfgr().modify(|reg| reg.set_res(resolution.into()));
     }
 
+    /// Get the currently configured ADC resolution.
+    pub fn resolution(&self) -> Resolution {
+        T::regs().cfgr().read().res()
+    }
+
+    /// Configure discontinuous mode for the regular sequence.
+    ///
+    /// With `Some(n)`, each trigger converts the next `n` channels of the regular sequence
+    /// instead of the whole sequence, so a long sequence is spread over several triggers. `None`
+    /// converts the whole sequence per trigger. Returns [`Error::InvalidConfig`] if `n` is not in
+    /// the 1 to 8 hardware range or longer than the currently programmed sequence. Ongoing
+    /// conversions are stopped first.
+    pub fn set_discontinuous(&mut self, group_len: Option<u8>) -> Result<(), Error> {
+        if let Some(n) = group_len {
+            let sequence_len = T::regs().sqr1().read().l() + 1;
+            if !(1..=8).contains(&n) || n > sequence_len {
+                return Err(Error::InvalidConfig);
+            }
+        }
+
+        self.stop_conversions();
+        T::regs().cfgr().modify(|w| {
+            w.set_discen(group_len.is_some());
+            if let Some(n) = group_len {
+                w.set_discnum(n - 1);
+            }
+        });
+
+        Ok(())
+    }
+
+    /// Override the boost mode, which sets the analog bias current for the ADC clock range.
+    ///
+    /// The boost mode is already selected from the ADC clock in [`Adc::new`]. Too low a setting
+    /// for the actual ADC clock gives inaccurate conversions, too high a setting only costs
+    /// power. Ongoing conversions are stopped first.
+    #[cfg(stm32h7)]
+    pub fn set_boost(&mut self, boost: Boost) {
+        self.stop_conversions();
+        T::regs().cr().modify(|w| w.set_boost(boost));
+    }
+
+    /// Enable or disable the auto-delayed conversion mode.
+    ///
+    /// With auto-delay, a new conversion doesn't start until the previous result has been read
+    /// from the data register, so conversions can't overrun but are paced by the reader and no
+    /// longer follow the trigger timing exactly. Ongoing conversions are stopped first.
+    pub fn set_auto_delay(&mut self, enable: bool) {
+        self.stop_conversions();
+        T::regs().cfgr().modify(|w| w.set_autdly(enable));
+    }
+
+    /// Convert a raw sample to volts, given the reference voltage in volts.
+    ///
+    /// The sample is scaled by the full-scale count of the currently configured resolution.
+    pub fn to_volts(&self, sample: u16, vref_volts: f32) -> f32 {
+        sample as f32 * vref_volts / super::resolution_to_max_count(self.resolution()) as f32
+    }
+
+    /// Apply a complete configuration at once.
+    ///
+    /// The configuration is validated before any register is written, so on error the ADC is
+    /// left untouched. Ongoing regular and injected conversions are stopped first, as the
+    /// configuration registers can only be written while no conversion is running. The ADC
+    /// stays enabled and ready for the next read.
+    pub fn apply_config(&mut self, config: &AdcConfig) -> Result<(), Error> {
+        #[cfg(stm32g4)]
+        let supported = matches!(
+            config.resolution,
+            Resolution::BITS12 | Resolution::BITS10 | Resolution::BITS8
+        );
+        #[cfg(stm32h7)]
+        let supported = config.resolution.to_bits() != 4;
+        if !supported {
+            return Err(Error::InvalidConfig);
+        }
+
+        self.stop_conversions();
+
+        T::regs().cfgr().modify(|w| {
+            w.set_res(config.resolution);
+            w.set_dmngt(config.data_management.into());
+        });
+        self.set_uniform_sample_time(config.sample_time);
+
+        Ok(())
+    }
+
+    /// Stop ongoing regular and injected conversions.
+    fn stop_conversions(&mut self) {
+        let r = T::regs();
+        let cr = r.cr().read();
+        if cr.adstart() {
+            r.cr().modify(|w| w.set_adstp(Adstp::STOP));
+        }
+        if cr.jadstart() {
+            r.cr().modify(|w| w.set_jadstp(Adstp::STOP));
+        }
+        while r.cr().read().adstart() || r.cr().read().jadstart() {}
+    }
+
+    /// Select how regular conversion data is routed.
+    ///
+    /// This is the DMNGT field on H7 and the DMAEN/DMACFG bits on G4. The DFSDM routing is
+    /// only available on H7. The blocking reads of this driver read the data register, so
+    /// select [`DataManagement::Register`] again before using them.
+    pub fn set_data_management(&mut self, mode: DataManagement) {
+        T::regs().cfgr().modify(|w| w.set_dmngt(mode.into()));
+    }
+
+    /// Program a hardware offset that is subtracted from every conversion of `channel`.
+    ///
+    /// There are only 4 offset registers per ADC, so at most 4 channels can have an offset at the
+    /// same time. Calling this again for a channel that already has an offset updates its slot,
+    /// and `enable = false` releases the slot. Returns [`Error::OffsetSlotsFull`] if all slots
+    /// are assigned to other channels.
+    ///
+    /// Subtracting an offset can make the result negative, which is handled differently per
+    /// family:
+    /// - G4: unsigned saturation (SATEN) is enabled, so the result is clamped at 0 and can be
+    ///   read with [`Adc::blocking_read`]. The offset is 12 bits, a larger one returns
+    ///   [`Error::InvalidConfig`].
+    /// - H7: signed saturation (SSATE) is enabled, so the result is a signed value saturated to
+    ///   the configured resolution. Read it with [`Adc::blocking_read_signed`].
+    pub fn set_offset(&mut self, channel: u8, offset: u16, enable: bool) -> Result<(), Error> {
+        let r = T::regs();
+        // On G4 bit 31 is OFFSET_EN rather than SSATE, and a zero offset can be enabled.
+        #[cfg(stm32g4)]
+        let enabled = |ofr: pac::adc::regs::Ofr| ofr.ssate();
+        #[cfg(stm32h7)]
+        let enabled = |ofr: pac::adc::regs::Ofr| ofr.offset1() != 0;
+        let assigned = |n: usize| {
+            let ofr = r.ofr(n).read();
+            enabled(ofr) && ofr.offset1_ch() == channel
+        };
+        let free = |n: usize| !enabled(r.ofr(n).read());
+
+        #[cfg(stm32g4)]
+        if offset > 0xFFF {
+            return Err(Error::InvalidConfig);
+        }
+
+        let slot = match (0..OFFSET_SLOTS).find(|&n| assigned(n)) {
+            Some(slot) => slot,
+            None if !enable => return Ok(()),
+            None => (0..OFFSET_SLOTS).find(|&n| free(n)).ok_or(Error::OffsetSlotsFull)?,
+        };
+
+        // On G4 SATEN is bit 25, which falls in the OFFSET field of the H7 layout.
+        #[cfg(stm32g4)]
+        let value = offset as u32 | 1 << 25;
+        #[cfg(stm32h7)]
+        let value = offset as u32;
+
+        r.ofr(slot).write(|w| {
+            if enable {
+                w.set_offset1_ch(channel);
+                w.set_offset1(value);
+                // OFFSET_EN on G4, SSATE on H7.
+                w.set_ssate(true);
+            }
+        });
+
+        Ok(())
+    }
+
+    /// Configure the injected group to convert `channels` in order.
+    ///
+    /// The injected group holds up to 4 channels. The injected trigger set with
+    /// [`Adc::set_injected_trigger`] is kept, by default the group is software triggered. The
+    /// current sample time is applied to every channel. On H7 the channels are also preselected
+    /// in PCSEL, and stay preselected across the regular reads of this driver. Results are read
+    /// with [`Adc::injected_result`]. Returns [`Error::InvalidConfig`] unless `channels` holds
+    /// 1 to 4 channels.
+    pub fn set_injected_sequence(&mut self, channels: &[u8]) -> Result<(), Error> {
+        if channels.is_empty() || channels.len() > 4 {
+            return Err(Error::InvalidConfig);
+        }
+
+        self.prepared_channel = None;
+        for &ch in channels {
+            Self::set_channel_sample_time(ch, self.sample_time);
+        }
+        Self::preselect(channels);
+
+        T::regs().jsqr().modify(|w| {
+            w.set_jl(channels.len() as u8 - 1);
+            for (rank, &ch) in channels.iter().enumerate() {
+                w.set_jsq1(rank, ch);
+            }
+        });
+        self.injected_len = channels.len() as u8;
+
+        Ok(())
+    }
+
+    /// Set the injected group trigger, independently of the regular group trigger.
+    ///
+    /// `source` is the JEXTSEL value from the reference manual and `edge` selects the active
+    /// edge, or [`Exten::DISABLED`] for software triggering. The injected group can therefore
+    /// sample on a different edge than the regular group, e.g. both edges of the same PWM
+    /// signal. An external injected trigger cannot be combined with automatic injected
+    /// conversion, so this disables JAUTO when `edge` is not [`Exten::DISABLED`], stopping
+    /// ongoing conversions first as JAUTO can only be written while the ADC is idle.
+    pub fn set_injected_trigger(&mut self, edge: Exten, source: u8) {
+        if edge != Exten::DISABLED {
+            self.stop_conversions();
+            T::regs().cfgr().modify(|w| w.set_jauto(false));
+        }
+
+        T::regs().jsqr().modify(|w| {
+            w.set_jextsel(source);
+            w.set_jexten(Jexten::from_bits(edge.to_bits()));
+        });
+    }
+
+    /// Start the injected group.
+    ///
+    /// With a software trigger this converts the group once. With an external trigger this arms
+    /// the group, which is then converted on every trigger edge.
+    pub fn start_injected(&mut self) {
+        T::regs().isr().write(|w| {
+            w.set_jeoc(true);
+            w.set_jeos(true);
+        });
+        T::regs().cr().modify(|w| w.set_jadstart(true));
+    }
+
+    /// Read the last result of the injected conversion at `rank` (0 to 3).
+    pub fn injected_result(&self, rank: usize) -> u16 {
+        T::regs().jdr(rank).read().jdata() as u16
+    }
+
+    /// Enable or disable automatic injected conversion (JAUTO).
+    ///
+    /// When enabled, the injected group is converted automatically after each regular
+    /// sequence, keeping both groups synchronized without a separate injected trigger. External
+    /// injected triggers are ignored in this mode, so the injected trigger is set to software.
+    ///
+    /// Returns [`Error::NotReady`] if no injected group has been configured with
+    /// [`Adc::set_injected_sequence`]. Ongoing conversions are stopped first, as JAUTO can only
+    /// be written while the ADC is idle.
+    pub fn set_auto_injected(&mut self, enable: bool) -> Result<(), Error> {
+        if enable && self.injected_len == 0 {
+            return Err(Error::NotReady);
+        }
+
+        self.stop_conversions();
+        if enable {
+            T::regs().jsqr().modify(|w| w.set_jexten(Jexten::DISABLED));
+        }
+        T::regs().cfgr().modify(|w| {
+            w.set_jdiscen(false);
+            w.set_jauto(enable);
+        });
+
+        Ok(())
+    }
+
+    /// Enable or disable dual simultaneous injected mode for the ADC pair `master` and `slave`.
+    ///
+    /// In this mode the injected trigger of `master` starts the injected groups of both ADCs at
+    /// the same instant, e.g. to sample two motor phase currents together. Only the ADCs sharing
+    /// a common block form a pair, with the lower numbered one as master: ADC1/ADC2 on H7, and
+    /// ADC1/ADC2 or ADC3/ADC4 on G4. The trigger of `slave` is ignored while the mode is enabled.
+    ///
+    /// Both injected groups must be configured first with [`Adc::set_injected_sequence`], with
+    /// the same length, and should use the same sample times so the conversions stay aligned.
+    /// Returns [`Error::NotReady`] otherwise, and [`Error::InvalidConfig`] if the ADCs are not a
+    /// master and slave pair. Ongoing conversions of both ADCs are stopped, as the mode can only
+    /// be changed while they are idle.
+    pub fn set_dual_injected<U: Instance>(
+        master: &mut Self,
+        slave: &mut Adc<'_, U>,
+        enable: bool,
+    ) -> Result<(), Error> {
+        if !Self::is_dual_pair::<U>() {
+            return Err(Error::InvalidConfig);
+        }
+
+        if enable && (master.injected_len == 0 || master.injected_len != slave.injected_len) {
+            return Err(Error::NotReady);
+        }
+
+        master.stop_conversions();
+        slave.stop_conversions();
+
+        let dual = if enable { Dual::DUALJ } else { Dual::INDEPENDENT };
+        T::common_regs().ccr().modify(|w| w.set_dual(dual));
+
+        Ok(())
+    }
+
+    /// Check whether this ADC is the master of a dual mode pair with `U` as slave.
+    ///
+    /// ADC3 to ADC5 of the G4 share one common block, but only ADC3 and ADC4 form a pair.
+    fn is_dual_pair<U: Instance>() -> bool {
+        matches!((Self::instance_number(), Adc::<U>::instance_number()), (1, 2) | (3, 4))
+    }
+
+    /// Convert the injected groups of a pair in dual simultaneous injected mode.
+    ///
+    /// This starts the injected group of `master`, which also starts `slave`, or arms both on an
+    /// external trigger, and waits until both sequences are complete. Entry `i` of the result
+    /// holds the results of rank `i` of the master and the slave. Unlike the regular group
+    /// there is no combined data register for injected conversions, each result is read from
+    /// the JDRx register of its own ADC. Ranks beyond the injected length read as 0.
+    ///
+    /// Returns [`Error::NotReady`] if the pair is not in dual simultaneous injected mode, see
+    /// [`Adc::set_dual_injected`], as the slave would then never be started.
+    pub fn blocking_read_dual_injected<U: Instance>(
+        master: &mut Self,
+        slave: &mut Adc<'_, U>,
+    ) -> Result<[(u16, u16); 4], Error> {
+        if !Self::is_dual_pair::<U>() || T::common_regs().ccr().read().dual() != Dual::DUALJ {
+            return Err(Error::NotReady);
+        }
+
+        U::regs().isr().write(|w| {
+            w.set_jeoc(true);
+            w.set_jeos(true);
+        });
+        master.start_injected();
+
+        while !T::regs().isr().read().jeos() || !U::regs().isr().read().jeos() {}
+
+        let mut results = [(0, 0); 4];
+        for (rank, result) in results.iter_mut().enumerate().take(master.injected_len as usize) {
+            *result = (master.injected_result(rank), slave.injected_result(rank));
+        }
+        Ok(results)
+    }
+
+    /// Configure analog watchdog 2 to monitor `channels` against a common `low`/`high` window.
+    ///
+    /// Any regular or injected conversion of one of the channels outside the window sets the
+    /// `awd2` flag, see [`Adc::watchdog_flags`]. An empty `channels` slice disables the watchdog.
+    /// The thresholds are compared with results at the current resolution, so returns
+    /// [`Error::InvalidConfig`] if `low` is above `high` or `high` is above its full scale.
+    ///
+    /// Only available on H7. The G4 has 8-bit AWD2 thresholds in a TR2 register at a different
+    /// offset, which this register block does not describe.
+    #[cfg(stm32h7)]
+    pub fn configure_watchdog2(&mut self, channels: &[u8], low: u32, high: u32) -> Result<(), Error> {
+        self.check_watchdog_window(low, high)?;
+
+        T::regs().ltr2().write(|w| w.set_ltr2(low));
+        T::regs().htr2().write(|w| w.set_htr2(high));
+        T::regs().awd2cr().write(|w| {
+            for &ch in channels {
+                w.set_awd2ch(ch as usize, true);
+            }
+        });
+
+        Ok(())
+    }
+
+    /// Configure analog watchdog 3 to monitor `channels` against a common `low`/`high` window.
+    ///
+    /// Any regular or injected conversion of one of the channels outside the window sets the
+    /// `awd3` flag, see [`Adc::watchdog_flags`]. An empty `channels` slice disables the watchdog.
+    /// The thresholds are compared with results at the current resolution, so returns
+    /// [`Error::InvalidConfig`] if `low` is above `high` or `high` is above its full scale.
+    ///
+    /// Only available on H7. The G4 has 8-bit AWD3 thresholds in a TR3 register at a different
+    /// offset, which this register block does not describe.
+    #[cfg(stm32h7)]
+    pub fn configure_watchdog3(&mut self, channels: &[u8], low: u32, high: u32) -> Result<(), Error> {
+        self.check_watchdog_window(low, high)?;
+
+        T::regs().ltr3().write(|w| w.set_ltr3(low));
+        T::regs().htr3().write(|w| w.set_htr3(high));
+        T::regs().awd3cr().write(|w| {
+            for &ch in channels {
+                w.set_awd3ch(ch as usize, true);
+            }
+        });
+
+        Ok(())
+    }
+
+    #[cfg(stm32h7)]
+    fn check_watchdog_window(&self, low: u32, high: u32) -> Result<(), Error> {
+        if low > high || high > super::resolution_to_max_count(self.resolution()) {
+            return Err(Error::InvalidConfig);
+        }
+        Ok(())
+    }
+
+    /// Read the analog watchdog flags.
+    ///
+    /// The hardware only reports which watchdog tripped, not which of its channels caused it.
+    /// To find the channel, read the monitored channels and compare them against the window.
+    pub fn watchdog_flags(&self) -> WatchdogFlags {
+        let isr = T::regs().isr().read();
+        WatchdogFlags {
+            awd1: isr.awd1(),
+            awd2: isr.awd2(),
+            awd3: isr.awd3(),
+        }
+    }
+
+    /// Clear all analog watchdog flags.
+    pub fn clear_watchdog_flags(&mut self) {
+        T::regs().isr().write(|w| {
+            w.set_awd1(true);
+            w.set_awd2(true);
+            w.set_awd3(true);
+        });
+    }
+
+    /// Check the end of conversion flag, set after each conversion of a regular sequence.
+    ///
+    /// The flag is also cleared by hardware when the data register is read, so in a sequence
+    /// read without DMA, wait for it, then read the result to arm it for the next channel.
+    pub fn eoc(&self) -> bool {
+        T::regs().isr().read().eoc()
+    }
+
+    /// Clear the end of conversion flag.
+    pub fn clear_eoc(&mut self) {
+        T::regs().isr().write(|w| w.set_eoc(true));
+    }
+
+    /// Check the end of sequence flag, set after the last conversion of a regular sequence.
+    ///
+    /// Unlike [`Adc::eoc`], reading the data register doesn't clear it, it must be cleared with
+    /// [`Adc::clear_eos`] before the next sequence is started.
+    pub fn eos(&self) -> bool {
+        T::regs().isr().read().eos()
+    }
+
+    /// Clear the end of sequence flag.
+    pub fn clear_eos(&mut self) {
+        T::regs().isr().write(|w| w.set_eos(true));
+    }
+
     /// Perform a single conversion.
     fn convert(&mut self) -> u16 {
-        T::regs().isr().modify(|reg| {
+        self.convert_raw() as u16
+    }
+
+    /// Perform a single conversion and return the whole data register.
+    fn convert_raw(&mut self) -> u32 {
+        // With a linked trigger the previous read left the ADC disarmed, so the result below
+        // always comes from a conversion started by this call. Clearing OVR as well lets DR
+        // take that result, since it is not overwritten while OVR is set.
+        T::regs().isr().write(|reg| {
             reg.set_eos(true);
             reg.set_eoc(true);
+            reg.set_ovr(true);
         });
 
         // Start conversion
@@ -269,7 +1258,15 @@ impl<'d, T: Instance> Adc<'d, T> {
             // spin
         }
 
-        T::regs().dr().read().0 as u16
+        let data = T::regs().dr().read().0;
+
+        // ADSTART stays set while an external trigger is selected. Disarm so later trigger
+        // events don't keep converting into DR and overrun it before the next read.
+        if T::regs().cfgr().read().exten() != Exten::DISABLED {
+            self.stop_conversions();
+        }
+
+        data
     }
 
     /// Read an ADC pin.
@@ -288,24 +1285,708 @@ impl<'d, T: Instance> Adc<'d, T> {
         self.read_channel(channel.channel())
     }
 
-    fn read_channel(&mut self, channel: u8) -> u16 {
-        // Configure channel
-        Self::set_channel_sample_time(channel, self.sample_time);
+    /// Read an ADC channel.
+    pub fn blocking_read(&mut self, channel: &mut impl AdcChannel<T>) -> u16 {
+        channel.setup();
+
+        self.read_channel(channel.channel())
+    }
+
+    /// Read an ADC channel whose result can be negative, e.g. with an offset set by
+    /// [`Adc::set_offset`] on H7.
+    ///
+    /// The data register holds the signed result sign extended to 32 bits. Without an offset,
+    /// or on G4 where offset results are clamped at 0, this returns the same value as
+    /// [`Adc::blocking_read`].
+    pub fn blocking_read_signed(&mut self, channel: &mut impl AdcChannel<T>) -> i32 {
+        channel.setup();
+        self.prepare_channel(channel.channel());
+
+        self.convert_raw() as i32
+    }
+
+    /// Read an ADC channel after `discard` throwaway conversions.
+    ///
+    /// The sampling capacitor still holds the charge of the previously converted channel when the
+    /// multiplexer switches, and a high impedance source may not fully overwrite it within one
+    /// sample time, leaking the previous channel into the first result. Each discarded conversion
+    /// brings the capacitor closer to the new input. One or two discards are usually enough;
+    /// if more are needed, a longer sample time is the better fix.
+    pub fn blocking_read_after_discard(&mut self, channel: &mut impl AdcChannel<T>, discard: u8) -> u16 {
+        channel.setup();
+        let channel = channel.channel();
+
+        for _ in 0..discard {
+            self.read_channel(channel);
+        }
+
+        self.read_channel(channel)
+    }
+
+    /// Check whether the current sample time is long enough for the source driving `channel`.
+    ///
+    /// The channel is read with the current sample time and with the longest one. A high source
+    /// impedance doesn't let the sampling capacitor settle within a short sample time, which
+    /// shows up as a difference between the two readings. The reading is considered stable if
+    /// they differ by no more than 1/256 of full scale (16 LSB at 12 bits), leaving room for
+    /// ordinary conversion noise. The configured sample time is restored afterwards.
+    pub fn check_settling(&mut self, channel: &mut impl AdcChannel<T>) -> bool {
+        let sample_time = self.sample_time;
+        let short = self.blocking_read(channel);
+
+        self.set_sample_time(SampleTime::from_bits(7));
+        let long = self.blocking_read(channel);
+        self.set_sample_time(sample_time);
+
+        let threshold = (super::resolution_to_max_count(self.resolution()) + 1) / 256;
+        (short as u32).abs_diff(long as u32) <= threshold.max(1)
+    }
+
+    /// Read an ADC channel at `resolution`, restoring the configured resolution afterwards.
+    ///
+    /// This is slower than reading at a fixed resolution, as CFGR is written twice per read.
+    pub fn read_with_resolution(&mut self, channel: &mut impl AdcChannel<T>, resolution: Resolution) -> u16 {
+        let previous = self.resolution();
+        T::regs().cfgr().modify(|w| w.set_res(resolution));
+        let sample = self.blocking_read(channel);
+        T::regs().cfgr().modify(|w| w.set_res(previous));
+
+        sample
+    }
+
+    /// Read an ADC channel and return the voltage in millivolts, corrected with `calibration`.
+    ///
+    /// The result saturates at 0 and `u16::MAX`, see [`TwoPointCalibration::to_millivolts`].
+    pub fn blocking_read_calibrated(
+        &mut self,
+        channel: &mut impl AdcChannel<T>,
+        calibration: &TwoPointCalibration,
+    ) -> u16 {
+        let raw = self.blocking_read(channel);
+
+        calibration.to_millivolts(raw)
+    }
+
+    /// Read an ADC channel through the exponential moving average `filter`.
+    ///
+    /// Each read moves the filtered value `alpha` (0.0 to 1.0) of the way towards the new
+    /// sample, so smaller values smooth more and respond slower. The first read with a new
+    /// filter, or the first after [`FilterState::reset`], initializes it with the sample. The
+    /// state is kept with 16 fractional bits and `alpha` is rounded to a multiple of 2^-16.
+    pub fn blocking_read_filtered(
+        &mut self,
+        channel: &mut impl AdcChannel<T>,
+        filter: &mut FilterState,
+        alpha: f32,
+    ) -> u16 {
+        let alpha = (alpha.clamp(0.0, 1.0) * 65536.0 + 0.5) as i64;
+        let sample = (self.blocking_read(channel) as i64) << 16;
+
+        let filtered = match filter.0 {
+            Some(prev) => prev as i64 + (((sample - prev as i64) * alpha) >> 16),
+            None => sample,
+        };
+        filter.0 = Some(filtered as u32);
+
+        ((filtered + 0x8000) >> 16).min(u16::MAX as i64) as u16
+    }
+
+    /// Read the voltage in millivolts of a node behind an external divider.
+    ///
+    /// The input voltage is the reading corrected with `calibration` if given, and otherwise
+    /// scaled to [`VREF_DEFAULT_MV`]. It is multiplied by
+    /// `numerator / denominator`, e.g. 3 and 1 for a divider that divides by 3, assuming the
+    /// divider is linear, i.e. its output impedance is low compared to the ADC input. The
+    /// scaling is done in 64 bits and the result saturates at `u16::MAX`. Returns
+    /// [`Error::InvalidConfig`] if `denominator` is zero.
+    pub fn blocking_read_scaled(
+        &mut self,
+        channel: &mut impl AdcChannel<T>,
+        calibration: Option<&TwoPointCalibration>,
+        numerator: u32,
+        denominator: u32,
+    ) -> Result<u16, Error> {
+        if denominator == 0 {
+            return Err(Error::InvalidConfig);
+        }
+
+        let mv = match calibration {
+            Some(calibration) => self.blocking_read_calibrated(channel, calibration) as u64,
+            None => {
+                let max = super::resolution_to_max_count(self.resolution());
+                self.blocking_read(channel) as u64 * VREF_DEFAULT_MV as u64 / max as u64
+            }
+        };
+
+        Ok((mv * numerator as u64 / denominator as u64).min(u16::MAX as u64) as u16)
+    }
+
+    /// Read an ADC channel and return the result as a percentage of full scale, 0.0 to 100.0.
+    ///
+    /// The full scale is taken from the resolution configured at the time of the read.
+    pub fn blocking_read_percent(&mut self, channel: &mut impl AdcChannel<T>) -> f32 {
+        let sample = self.blocking_read(channel);
+
+        sample as f32 * 100.0 / super::resolution_to_max_count(self.resolution()) as f32
+    }
+
+    /// Read an ADC channel and return the value together with the time the conversion completed.
+    ///
+    /// The timestamp is taken right after the end of the conversion, so it lags the actual
+    /// sampling instant by the conversion time, which is constant for a given configuration.
+    #[cfg(feature = "time")]
+    pub fn blocking_read_timed(&mut self, channel: &mut impl AdcChannel<T>) -> (u16, embassy_time::Instant) {
+        let value = self.blocking_read(channel);
+        let now = embassy_time::Instant::now();
+
+        (value, now)
+    }
+
+    /// Convert `regular` as one regular sequence followed by the injected group, and return all
+    /// results of that single trigger together.
+    ///
+    /// If an injected group is configured with [`Adc::set_injected_sequence`], automatic
+    /// injected conversion is enabled for the duration of the read, see
+    /// [`Adc::set_auto_injected`], so the injected group is converted right after the regular
+    /// sequence of the same trigger and both parts of the frame always correspond. The previous
+    /// JAUTO, JDISCEN and injected trigger edge are restored before returning. The regular
+    /// sequence is started in software, or armed if an external regular trigger is configured
+    /// and disarmed again once the frame is in, and this blocks until the frame is complete.
+    /// The current sample time is used for the regular channels.
+    ///
+    /// The data layout is described on [`AdcFrame`]. The timestamp is taken when the first
+    /// regular conversion completes, minus its sample and conversion time, so it estimates the
+    /// trigger instant to within the polling latency and the resolution of the time driver.
+    /// Returns [`Error::Overrun`] if a regular result was overwritten before it could be read,
+    /// and [`Error::InvalidConfig`] unless N is 1 to 16.
+    #[cfg(feature = "time")]
+    pub fn blocking_read_frame<const N: usize>(
+        &mut self,
+        mut regular: [&mut dyn AdcChannel<T>; N],
+    ) -> Result<AdcFrame<N>, Error> {
+        if !(1..=16).contains(&N) {
+            return Err(Error::InvalidConfig);
+        }
+
+        // The injected channels were preselected by `set_injected_sequence`.
+        let mut ids = [0u8; N];
+        for (id, channel) in ids.iter_mut().zip(regular.iter_mut()) {
+            channel.setup();
+            *id = channel.channel();
+        }
+        let injected_len = self.injected_len as usize;
+
+        self.prepared_channel = None;
+        for &ch in &ids {
+            Self::set_channel_sample_time(ch, self.sample_time);
+        }
+        let cfgr = T::regs().cfgr().read();
+        let jexten = T::regs().jsqr().read().jexten();
+        if injected_len > 0 {
+            unwrap!(self.set_auto_injected(true));
+        }
+        Self::preselect(&ids);
+        Self::set_regular_sequence(&ids);
+
+        T::regs().isr().write(|w| {
+            w.set_eos(true);
+            w.set_eoc(true);
+            w.set_jeos(true);
+            w.set_jeoc(true);
+            w.set_ovr(true);
+        });
+        T::regs().cr().modify(|w| w.set_adstart(true));
+
+        let conversion = embassy_time::Duration::from_nanos(
+            self.conversion_half_cycles() as u64 * 500_000_000 / self.clock.0 as u64,
+        );
+        let mut frame = AdcFrame {
+            timestamp: embassy_time::Instant::from_ticks(0),
+            regular: [0; N],
+            injected: [0; 4],
+        };
+
+        let result = self.read_frame_results(&mut frame, conversion, injected_len);
+
+        if injected_len > 0 || cfgr.exten() != Exten::DISABLED {
+            self.stop_conversions();
+        }
+        if injected_len > 0 {
+            T::regs().cfgr().modify(|w| {
+                w.set_jauto(cfgr.jauto());
+                w.set_jdiscen(cfgr.jdiscen());
+            });
+            T::regs().jsqr().modify(|w| w.set_jexten(jexten));
+        }
+
+        result.map(|()| frame)
+    }
+
+    /// Wait for and read the results of a frame started by [`Adc::blocking_read_frame`].
+    #[cfg(feature = "time")]
+    fn read_frame_results<const N: usize>(
+        &mut self,
+        frame: &mut AdcFrame<N>,
+        conversion: embassy_time::Duration,
+        injected_len: usize,
+    ) -> Result<(), Error> {
+        // EOC is cleared by reading DR.
+        for (i, result) in frame.regular.iter_mut().enumerate() {
+            loop {
+                let isr = T::regs().isr().read();
+                if isr.ovr() {
+                    self.stop_conversions();
+                    T::regs().isr().write(|w| w.set_ovr(true));
+                    return Err(Error::Overrun);
+                }
+                if isr.eoc() {
+                    break;
+                }
+            }
+            if i == 0 {
+                let now = embassy_time::Instant::now();
+                frame.timestamp = now.checked_sub(conversion).unwrap_or(now);
+            }
+            *result = T::regs().dr().read().0 as u16;
+        }
+
+        if injected_len > 0 {
+            while !T::regs().isr().read().jeos() {}
+            for (rank, result) in frame.injected.iter_mut().enumerate().take(injected_len) {
+                *result = self.injected_result(rank);
+            }
+        }
+
+        Ok(())
+    }
 
+    /// Read an ADC channel, giving up with [`Error::Timeout`] if the conversion doesn't finish
+    /// within `timeout`.
+    ///
+    /// The end of the conversion is signalled by the ADC interrupt, so the executor is free to
+    /// run other tasks or sleep in the meantime. On timeout the conversion is aborted, leaving
+    /// the ADC ready for the next read. This must not be used while a callback is registered
+    /// with [`Adc::on_sample`], which consumes the result.
+    #[cfg(feature = "time")]
+    pub async fn read_timeout(
+        &mut self,
+        _irq: impl interrupt::typelevel::Binding<T::Interrupt, InterruptHandler<T>>,
+        channel: &mut impl AdcChannel<T>,
+        timeout: embassy_time::Duration,
+    ) -> Result<u16, Error> {
+        channel.setup();
+        self.prepare_channel(channel.channel());
+
+        T::regs().isr().write(|w| {
+            w.set_eos(true);
+            w.set_eoc(true);
+        });
+        T::regs().ier().modify(|w| w.set_eosie(true));
+        T::Interrupt::unpend();
+        unsafe { T::Interrupt::enable() };
+        T::regs().cr().modify(|w| w.set_adstart(true));
+
+        let done = core::future::poll_fn(|cx| {
+            T::state().waker.register(cx.waker());
+
+            if T::regs().isr().read().eos() {
+                core::task::Poll::Ready(())
+            } else {
+                core::task::Poll::Pending
+            }
+        });
+
+        if embassy_time::with_timeout(timeout, done).await.is_err() {
+            T::regs().ier().modify(|w| w.set_eosie(false));
+            self.stop_conversions();
+            return Err(Error::Timeout);
+        }
+
+        Ok(T::regs().dr().read().0 as u16)
+    }
+
+    /// Read one channel on this ADC and one on `other`, both started by the same external trigger.
+    ///
+    /// Both ADCs are armed on the trigger selected by `extsel` (see the EXTSEL table of the
+    /// reference manual, the same value must select the same trigger on both ADCs) with the
+    /// given `edge`, then this waits for both conversions to finish and returns the results as
+    /// `(self, other)`. The two sampling instants are aligned to within one ADC clock cycle
+    /// provided both ADCs run from the same clock with the same prescaler and sample time.
+    /// Afterwards both ADCs are returned to software triggering. Returns
+    /// [`Error::InvalidConfig`] if `edge` is [`Exten::DISABLED`].
+    pub fn blocking_read_triggered_pair<T2: Instance>(
+        &mut self,
+        other: &mut Adc<'_, T2>,
+        channel: &mut impl AdcChannel<T>,
+        other_channel: &mut impl AdcChannel<T2>,
+        extsel: u8,
+        edge: Exten,
+    ) -> Result<(u16, u16), Error> {
+        if edge == Exten::DISABLED {
+            return Err(Error::InvalidConfig);
+        }
+
+        channel.setup();
+        other_channel.setup();
+        self.prepare_channel(channel.channel());
+        other.prepare_channel(other_channel.channel());
+
+        T::regs().cfgr().modify(|w| {
+            w.set_extsel(extsel);
+            w.set_exten(edge);
+        });
+        T2::regs().cfgr().modify(|w| {
+            w.set_extsel(extsel);
+            w.set_exten(edge);
+        });
+
+        // Arm both ADCs, conversions start on the next trigger edge.
+        T::regs().isr().modify(|w| w.set_eos(true));
+        T2::regs().isr().modify(|w| w.set_eos(true));
+        T::regs().cr().modify(|w| w.set_adstart(true));
+        T2::regs().cr().modify(|w| w.set_adstart(true));
+
+        while !T::regs().isr().read().eos() || !T2::regs().isr().read().eos() {
+            // spin
+        }
+        let value = T::regs().dr().read().0 as u16;
+        let other_value = T2::regs().dr().read().0 as u16;
+
+        // Disarm before going back to software triggering.
+        for r in [T::regs(), T2::regs()] {
+            r.cr().modify(|w| w.set_adstp(Adstp::STOP));
+            while r.cr().read().adstart() {}
+        }
+        self.configure();
+        other.configure();
+
+        Ok((value, other_value))
+    }
+
+    /// Arm the regular group to convert `channel` on every `edge` of an external trigger.
+    ///
+    /// This is intended for a timer TRGO selected with `extsel`, firing at `trigger_rate`.
+    /// Triggers arriving while a conversion is running are silently ignored by the ADC, which
+    /// has no flag for a missed trigger, so it cannot be detected once conversions are running.
+    /// Since the conversion time is fixed, this instead returns [`Error::TriggerOverrun`] when
+    /// arming if the sample plus conversion time of the current sample time and resolution
+    /// doesn't fit in one trigger period. This check relies on `trigger_rate` matching the
+    /// actual trigger. Results are read with [`Adc::blocking_read_timer_triggered`], which only
+    /// reports results the caller was too slow to read, as [`Error::Overrun`]. Returns
+    /// [`Error::InvalidConfig`] if `edge` is [`Exten::DISABLED`].
+    ///
+    /// With [`Exten::BOTHEDGES`] a conversion starts on each transition of the trigger signal,
+    /// e.g. a timer output compare reference, doubling the sample rate. The conversion then has
+    /// to fit in half a trigger period, which is checked assuming the signal has a 50% duty
+    /// cycle. For other duty cycles, make sure it also fits in the shorter of the two phases.
+    pub fn start_timer_triggered(
+        &mut self,
+        channel: &mut impl AdcChannel<T>,
+        extsel: u8,
+        edge: Exten,
+        trigger_rate: Hertz,
+    ) -> Result<(), Error> {
+        if edge == Exten::DISABLED {
+            return Err(Error::InvalidConfig);
+        }
+
+        let half_cycles = self.conversion_half_cycles();
+        let edges_per_period = if edge == Exten::BOTHEDGES { 2 } else { 1 };
+        if half_cycles as u64 * trigger_rate.0 as u64 * edges_per_period >= 2 * self.clock.0 as u64 {
+            return Err(Error::TriggerOverrun);
+        }
+
+        channel.setup();
+        self.prepare_channel(channel.channel());
+
+        T::regs().cfgr().modify(|w| {
+            w.set_extsel(extsel);
+            w.set_exten(edge);
+        });
+        T::regs().isr().modify(|w| {
+            w.set_eoc(true);
+            w.set_ovr(true);
+        });
+        T::regs().cr().modify(|w| w.set_adstart(true));
+
+        Ok(())
+    }
+
+    /// Trigger regular conversions from the update event of timer `TIM`.
+    ///
+    /// Both sides are configured: the timer's TRGO is set to its update event (MMS), and the
+    /// regular group is set to trigger on `edge` of that TRGO with the EXTSEL value of the
+    /// timer on this family. Regular conversions started afterwards, e.g. by
+    /// [`Adc::blocking_read`] or [`Adc::blocking_read_frame`], then wait for the next update
+    /// instead of starting immediately. Each of these reads disarms the ADC again once its
+    /// result is in, so update events between reads are ignored rather than overrunning the
+    /// data register, and a read always returns the conversion of the update that followed
+    /// it. The timer itself, its period and whether it runs, stay under the control of its
+    /// own driver. Use [`Adc::unlink_timer_trigger`] to return to software triggering.
+    ///
+    /// Supported timers are TIM1, TIM2, TIM3, TIM4, TIM6, TIM8 and TIM15 on G4 and H7, plus
+    /// TIM7 and TIM20 on G4, as far as the part has them. They map to the same EXTSEL value on
+    /// every ADC of the family.
+    ///
+    /// Returns [`Error::InvalidConfig`] if `edge` is [`Exten::DISABLED`].
+    pub fn link_timer_trigger<TIM: TriggerTimer>(&mut self, _tim: &TIM, edge: Exten) -> Result<(), Error> {
+        if edge == Exten::DISABLED {
+            return Err(Error::InvalidConfig);
+        }
+
+        // Safety: every `BasicInstance` has the CR2 register of the basic timer layout.
+        let tim = unsafe { crate::pac::timer::TimBasic::from_ptr(TIM::regs()) };
+        tim.cr2().modify(|w| w.set_mms(crate::pac::timer::vals::Mms::UPDATE));
+
+        self.stop_conversions();
+        T::regs().cfgr().modify(|w| {
+            w.set_extsel(TIM::EXTSEL);
+            w.set_exten(edge);
+        });
+
+        Ok(())
+    }
+
+    /// Get the sample plus conversion time of one channel in half ADC clock cycles, for the
+    /// current sample time and resolution.
+    fn conversion_half_cycles(&self) -> u32 {
+        // Sample and conversion times in half ADC clock cycles, RM0440 and RM0433.
+        #[cfg(stm32g4)]
+        const SAMPLE_HALF_CYCLES: [u32; 8] = [5, 13, 25, 49, 95, 185, 495, 1281];
+        #[cfg(stm32h7)]
+        const SAMPLE_HALF_CYCLES: [u32; 8] = [3, 5, 17, 33, 65, 129, 775, 1621];
+        let bits = 32 - super::resolution_to_max_count(self.resolution()).leading_zeros();
+        #[cfg(stm32g4)]
+        let conversion_half_cycles = 2 * bits + 1;
+        #[cfg(stm32h7)]
+        let conversion_half_cycles = bits + 1;
+
+        SAMPLE_HALF_CYCLES[self.sample_time.to_bits() as usize] + conversion_half_cycles
+    }
+
+    /// Wait for the next conversion started by [`Adc::start_timer_triggered`].
+    ///
+    /// Returns [`Error::Overrun`] if a result was produced before the previous one was read,
+    /// meaning the caller doesn't keep up with the trigger rate. The flag is cleared, so the
+    /// next call returns the following conversion.
+    pub fn blocking_read_timer_triggered(&mut self) -> Result<u16, Error> {
+        loop {
+            let isr = T::regs().isr().read();
+            if isr.ovr() {
+                T::regs().isr().modify(|w| w.set_ovr(true));
+                return Err(Error::Overrun);
+            }
+            if isr.eoc() {
+                return Ok(T::regs().dr().read().0 as u16);
+            }
+        }
+    }
+
+    /// Call `on_sample` with each regular conversion result, from the ADC interrupt.
+    ///
+    /// Combined with [`Adc::start_timer_triggered`], this acquires samples paced by hardware
+    /// without executor involvement. The callback runs in interrupt context at the priority of
+    /// the ADC interrupt, so it must be short, must not block or allocate, and can only share
+    /// data through statics, e.g. behind a critical section. A new result arriving before it
+    /// returns is reported as an overrun by the hardware. While it is registered, the blocking
+    /// reads must not be used, as the handler consumes the results. Use
+    /// [`Adc::clear_on_sample`] to unregister it.
+    pub fn on_sample(
+        &mut self,
+        _irq: impl interrupt::typelevel::Binding<T::Interrupt, InterruptHandler<T>>,
+        on_sample: fn(u16),
+    ) {
+        critical_section::with(|cs| T::state().on_sample.borrow(cs).set(Some(on_sample)));
+        T::regs().isr().write(|w| w.set_eoc(true));
+        T::regs().ier().modify(|w| w.set_eocie(true));
+
+        T::Interrupt::unpend();
+        unsafe { T::Interrupt::enable() };
+    }
+
+    /// Unregister the callback registered with [`Adc::on_sample`].
+    pub fn clear_on_sample(&mut self) {
+        T::regs().ier().modify(|w| w.set_eocie(false));
+        critical_section::with(|cs| T::state().on_sample.borrow(cs).set(None));
+    }
+
+    /// Undo [`Adc::link_timer_trigger`]: stop any armed conversion and return the regular
+    /// group to software triggering.
+    ///
+    /// The timer's TRGO selection is left as it is.
+    pub fn unlink_timer_trigger(&mut self) {
+        self.stop_conversions();
+        T::regs().cfgr().modify(|w| w.set_exten(Exten::DISABLED));
+        T::regs().isr().write(|w| w.set_ovr(true));
+    }
+
+    /// Stop timer triggered conversions and return to software triggering.
+    pub fn stop_timer_triggered(&mut self) {
+        self.stop_conversions();
+        self.configure();
+    }
+
+    /// Read up to 16 channels in one regular sequence and return the results in the same order.
+    ///
+    /// The channels are programmed into the sequencer once and converted back to back with the
+    /// current sample time, so the whole batch takes N times the sample plus conversion time.
+    /// Returns [`Error::Overrun`] if a result was overwritten before it could be read, and
+    /// [`Error::InvalidConfig`] unless N is 1 to 16.
+    pub fn blocking_read_all<const N: usize>(
+        &mut self,
+        mut channels: [&mut dyn AdcChannel<T>; N],
+    ) -> Result<[u16; N], Error> {
+        if !(1..=16).contains(&N) {
+            return Err(Error::InvalidConfig);
+        }
+
+        let mut ids = [0u8; N];
+        for (id, channel) in ids.iter_mut().zip(channels.iter_mut()) {
+            channel.setup();
+            *id = channel.channel();
+        }
+
+        self.prepared_channel = None;
+        for &ch in &ids {
+            Self::set_channel_sample_time(ch, self.sample_time);
+        }
+
+        let mut results = [0u16; N];
+        self.convert_sequence(&ids, &mut results)?;
+
+        Ok(results)
+    }
+
+    /// Preselect `channels` ahead of converting them in a sequence.
+    ///
+    /// On H7 a channel must be preselected in PCSEL to connect its input to the converter, and
+    /// preselecting all channels of a sequence at once avoids the setup per channel. The G4 has
+    /// no PCSEL, its inputs are connected by the sequencer alone, so this compiles to nothing
+    /// there. The channels are added to those already preselected, so the reads of this driver,
+    /// which add their own channels, keep this selection and the injected channels connected.
+    pub fn preselect_channels(&mut self, channels: &[u8]) {
+        self.prepared_channel = None;
+        Self::preselect(channels);
+    }
+
+    fn preselect(channels: &[u8]) {
         #[cfg(stm32h7)]
         {
             T::regs().cfgr2().modify(|w| w.set_lshift(0));
-            T::regs()
-                .pcsel()
-                .write(|w| w.set_pcsel(channel as _, Pcsel::PRESELECTED));
+            // Added to the existing selection, which includes the injected channels.
+            T::regs().pcsel().modify(|w| {
+                for &ch in channels {
+                    w.set_pcsel(ch as _, Pcsel::PRESELECTED);
+                }
+            });
+        }
+        #[cfg(not(stm32h7))]
+        let _ = channels;
+    }
+
+    /// Convert `channels` as one regular sequence, storing each result in `results`.
+    ///
+    /// The sample times must already be set. Anything calling this must clear
+    /// `prepared_channel`. Returns [`Error::Overrun`] if a result was overwritten before it
+    /// was read, e.g. because the reader was preempted, in which case the sequence is stopped
+    /// and the flag cleared so the next read starts clean.
+    fn convert_sequence(&mut self, channels: &[u8], results: &mut [u16]) -> Result<(), Error> {
+        Self::preselect(channels);
+        Self::set_regular_sequence(channels);
+
+        T::regs().isr().write(|w| {
+            w.set_eos(true);
+            w.set_eoc(true);
+            w.set_ovr(true);
+        });
+        T::regs().cr().modify(|w| w.set_adstart(true));
+
+        // EOC is cleared by reading DR.
+        for result in results.iter_mut() {
+            loop {
+                let isr = T::regs().isr().read();
+                if isr.ovr() {
+                    self.stop_conversions();
+                    T::regs().isr().write(|w| w.set_ovr(true));
+                    return Err(Error::Overrun);
+                }
+                if isr.eoc() {
+                    break;
+                }
+            }
+            *result = T::regs().dr().read().0 as u16;
         }
 
+        Ok(())
+    }
+
+    /// Read an ADC channel `samples` times and return the rounded mean.
+    ///
+    /// This is a software alternative to hardware oversampling that works the same on every
+    /// family. The samples are accumulated in a `u32`, which cannot overflow for any `u16`
+    /// sample count. A `samples` value of 0 is treated as 1.
+    pub fn blocking_read_averaged(&mut self, channel: &mut impl AdcChannel<T>, samples: u16) -> u16 {
+        let samples = samples.max(1) as u32;
+        channel.setup();
+        let channel = channel.channel();
+
+        let mut sum = 0u32;
+        for _ in 0..samples {
+            sum += self.read_channel(channel) as u32;
+        }
+
+        ((sum + samples / 2) / samples) as u16
+    }
+
+    fn read_channel(&mut self, channel: u8) -> u16 {
+        self.prepare_channel(channel);
+
+        self.convert()
+    }
+
+    /// Select `channel` as the only channel of the regular sequence.
+    ///
+    /// The selection is cached, so repeated reads of the same channel skip the SMPR, PCSEL and
+    /// SQR1 writes. Anything else that writes these registers must clear `prepared_channel`.
+    fn prepare_channel(&mut self, channel: u8) {
+        if self.prepared_channel == Some(channel) {
+            return;
+        }
+
+        // Configure channel
+        Self::set_channel_sample_time(channel, self.sample_time);
+        Self::preselect(&[channel]);
+
         T::regs().sqr1().write(|reg| {
             reg.set_sq(0, channel);
             reg.set_l(0);
         });
+        self.prepared_channel = Some(channel);
+    }
 
-        self.convert()
+    /// Program the regular sequence with 1 to 16 `channels`.
+    fn set_regular_sequence(channels: &[u8]) {
+        T::regs().sqr1().write(|w| {
+            w.set_l(channels.len() as u8 - 1);
+            for (i, &ch) in channels.iter().take(4).enumerate() {
+                w.set_sq(i, ch);
+            }
+        });
+        T::regs().sqr2().write(|w| {
+            for (i, &ch) in channels.iter().skip(4).take(5).enumerate() {
+                w.set_sq(i, ch);
+            }
+        });
+        T::regs().sqr3().write(|w| {
+            for (i, &ch) in channels.iter().skip(9).take(5).enumerate() {
+                w.set_sq(i, ch);
+            }
+        });
+        T::regs().sqr4().write(|w| {
+            for (i, &ch) in channels.iter().skip(14).take(2).enumerate() {
+                w.set_sq(i, ch);
+            }
+        });
     }
 
     fn set_channel_sample_time(ch: u8, sample_time: SampleTime) {
@@ -0,0 +1,64 @@
+//! This example shows how to play a melody on a piezo buzzer using PWM in the RP2040 chip.
+//!
+//! Connect a piezo buzzer between PIN_2 and ground.
+
+#![no_std]
+#![no_main]
+
+use defmt::*;
+use embassy_executor::Spawner;
+use embassy_rp::pwm::{Config, Pwm};
+use embassy_time::Timer;
+use {defmt_rtt as _, panic_probe as _};
+
+// Note frequencies in Hz.
+const C4: u32 = 262;
+const D4: u32 = 294;
+const E4: u32 = 330;
+const F4: u32 = 349;
+const G4: u32 = 392;
+const A4: u32 = 440;
+
+// (frequency, duration in ms), a frequency of 0 is a rest.
+const MELODY: [(u32, u64); 15] = [
+    (C4, 250),
+    (C4, 250),
+    (G4, 250),
+    (G4, 250),
+    (A4, 250),
+    (A4, 250),
+    (G4, 500),
+    (0, 250),
+    (F4, 250),
+    (F4, 250),
+    (E4, 250),
+    (E4, 250),
+    (D4, 250),
+    (D4, 250),
+    (C4, 500),
+];
+
+#[embassy_executor::main]
+async fn main(_spawner: Spawner) {
+    let p = embassy_rp::init(Default::default());
+
+    let mut pwm = Pwm::new_output_a(p.PWM_CH1, p.PIN_2, Config::default());
+
+    loop {
+        for (freq, duration) in MELODY {
+            if freq == 0 {
+                pwm.silence();
+            } else {
+                info!("playing {} Hz", freq);
+                pwm.play_tone(freq);
+            }
+            Timer::after_millis(duration).await;
+
+            // Short gap so that repeated notes are distinguishable.
+            pwm.silence();
+            Timer::after_millis(20).await;
+        }
+
+        Timer::after_secs(1).await;
+    }
+}
@@ -6,7 +6,7 @@ use defmt::{assert, assert_eq, assert_ne, *};
 use embassy_executor::Spawner;
 use embassy_rp::gpio::{Input, Level, Output, Pull};
 use embassy_rp::pwm::{Config, InputMode, Pwm};
-use embassy_time::Timer;
+use embassy_time::{Duration, Timer};
 use {defmt_rtt as _, panic_probe as _};
 
 #[embassy_executor::main]
@@ -137,6 +137,86 @@ async fn main(_spawner: Spawner) {
         assert_eq!(pwm.counter(), 1);
     }
 
+    // Test split
+    {
+        let pin1 = Input::new(&mut p9, Pull::None);
+        let pin2 = Input::new(&mut p11, Pull::None);
+        let pwm = Pwm::new_output_ab(&mut p.PWM_CH3, &mut p6, &mut p7, cfg.clone());
+        let (mut a, mut b) = pwm.split();
+        a.set_compare(0);
+        b.set_compare(10001);
+        Timer::after_millis(11).await;
+        assert!(pin1.is_low());
+        assert!(pin2.is_high());
+        a.set_compare(10001);
+        b.set_compare(0);
+        Timer::after_millis(11).await;
+        assert!(pin1.is_high());
+        assert!(pin2.is_low());
+    }
+
+    // Test play_tone and set_frequency, measured by looping back into slice 4
+    {
+        let mut pwm = Pwm::new_output_a(&mut p.PWM_CH3, &mut p6, cfg.clone());
+        pwm.play_tone(1000);
+        let freq = pwm.self_measure(&mut p.PWM_CH4, &mut p9);
+        info!("tone: {} Hz", freq);
+        assert!((900..=1100).contains(&freq));
+
+        pwm.set_frequency(2000);
+        Timer::after_millis(1).await;
+        let freq = pwm.self_measure(&mut p.PWM_CH4, &mut p9);
+        info!("set_frequency: {} Hz", freq);
+        assert!((1900..=2100).contains(&freq));
+    }
+
+    // Test set_output_pin_a, moving A off the looped back pin. The only other pin of B is
+    // GPIO23, which is in use on the Pico, so B stays where it is.
+    {
+        let mut cfg = cfg.clone();
+        cfg.compare_a = 10001;
+        cfg.compare_b = 10001;
+        let pin1 = Input::new(&mut p9, Pull::Down);
+        let pin2 = Input::new(&mut p11, Pull::Down);
+        let mut pwm = Pwm::new_output_ab(&mut p.PWM_CH3, &mut p6, &mut p7, cfg);
+        Timer::after_millis(1).await;
+        assert!(pin1.is_high());
+        assert!(pin2.is_high());
+
+        pwm.set_output_pin_a(&mut p.PIN_22);
+        Timer::after_millis(1).await;
+        assert!(pin1.is_low());
+        assert!(pin2.is_high());
+    }
+
+    // Test save and restore
+    {
+        let mut cfg = cfg.clone();
+        cfg.compare_a = 10001;
+        let pin1 = Input::new(&mut p9, Pull::None);
+        let mut pwm = Pwm::new_output_a(&mut p.PWM_CH3, &mut p6, cfg.clone());
+        let state = pwm.save();
+
+        cfg.compare_a = 0;
+        pwm.set_config(&cfg);
+        Timer::after_millis(11).await;
+        assert!(pin1.is_low());
+
+        pwm.restore(state);
+        Timer::after_millis(1).await;
+        assert!(pin1.is_high());
+        assert!(pwm.is_enabled());
+    }
+
+    // Test measure_pulse, measuring the 5 ms high phase of B
+    {
+        let _pwm = Pwm::new_output_b(&mut p.PWM_CH3, &mut p7, cfg.clone());
+        let mut input = Pwm::new_free(&mut p.PWM_CH5, Config::default());
+        let width = unwrap!(input.measure_pulse(&mut p11, Duration::from_millis(25)).await);
+        info!("pulse: {} us", width.as_micros());
+        assert!((4900..=5100).contains(&width.as_micros()));
+    }
+
     info!("Test OK");
     cortex_m::asm::bkpt();
 }